@@ -0,0 +1,230 @@
+use anyhow::{anyhow, Result};
+use console::{Emoji, Style};
+use dialoguer::Select;
+use similar::{ChangeTag, DiffOp, TextDiff};
+use std::path::{Path, PathBuf};
+
+use crate::is_binary;
+
+/// Suffix of the generated files written next to the existing ones
+pub(crate) const NEW_FILE_SUFFIX: &str = ".scaffold-new";
+
+/// What to do with a generated file which already exists in the target directory, when appending
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum ConflictPolicy {
+    /// Keep the existing file
+    Skip,
+    /// Replace the existing file by the generated one
+    Overwrite,
+    /// Write the generated file next to the existing one, with a `.scaffold-new` suffix
+    Rename,
+    /// Stop the generation, nothing is written
+    Fail,
+}
+
+/// Resolution of a conflict between an existing file and the generated one
+#[derive(Debug, PartialEq)]
+pub(crate) enum Resolution {
+    Keep,
+    Overwrite,
+    Rename,
+    /// Content of both files with conflict markers around their differences
+    Merge(Vec<u8>),
+}
+
+/// Decide what to do with the generated file, asking the user when no policy is given and
+/// running in a terminal. Without a terminal, the existing file is kept.
+pub(crate) fn resolve(
+    path: &Path,
+    existing: &[u8],
+    generated: &[u8],
+    policy: Option<ConflictPolicy>,
+) -> Result<Resolution> {
+    let policy = match policy {
+        Some(policy) => policy,
+        None if console::user_attended() => return ask(path, existing, generated),
+        None => ConflictPolicy::Skip,
+    };
+
+    match policy {
+        ConflictPolicy::Skip => Ok(Resolution::Keep),
+        ConflictPolicy::Overwrite => Ok(Resolution::Overwrite),
+        ConflictPolicy::Rename => Ok(Resolution::Rename),
+        ConflictPolicy::Fail => Err(anyhow!(
+            "cannot generate {} because it already exists",
+            path.to_string_lossy()
+        )),
+    }
+}
+
+fn ask(path: &Path, existing: &[u8], generated: &[u8]) -> Result<Resolution> {
+    let text = match (
+        std::str::from_utf8(existing),
+        std::str::from_utf8(generated),
+    ) {
+        (Ok(existing), Ok(generated)) if !is_binary(existing.as_bytes()) => {
+            Some((existing, generated))
+        }
+        _ => None,
+    };
+    let mut choices = vec![
+        "Keep the existing file",
+        "Overwrite it with the generated file",
+        "Write the generated file alongside (.scaffold-new)",
+    ];
+    if text.is_some() {
+        choices.extend(["Show the differences", "Merge them with conflict markers"]);
+    }
+
+    loop {
+        let choice = Select::new()
+            .with_prompt(format!(
+                "{} already exists, what do you want to do ?",
+                path.to_string_lossy()
+            ))
+            .items(&choices)
+            .default(0)
+            .interact()?;
+        match (choice, text) {
+            (0, _) => return Ok(Resolution::Keep),
+            (1, _) => return Ok(Resolution::Overwrite),
+            (2, _) => return Ok(Resolution::Rename),
+            (3, Some((existing, generated))) => print_diff(existing, generated),
+            (_, Some((existing, generated))) => {
+                return Ok(Resolution::Merge(
+                    merge_with_markers(existing, generated).into_bytes(),
+                ))
+            }
+            _ => unreachable!("no other choice for binary files. qed"),
+        }
+    }
+}
+
+fn print_diff(existing: &str, generated: &str) {
+    let diff = TextDiff::from_lines(existing, generated);
+    for change in diff.iter_all_changes() {
+        let (sign, style) = match change.tag() {
+            ChangeTag::Delete => ("-", Style::new().red()),
+            ChangeTag::Insert => ("+", Style::new().green()),
+            ChangeTag::Equal => (" ", Style::new().dim()),
+        };
+        print!("{}", style.apply_to(format!("{sign}{change}")));
+        if change.missing_newline() {
+            println!();
+        }
+    }
+}
+
+/// Both contents, with the lines which differ surrounded by git-like conflict markers
+pub(crate) fn merge_with_markers(existing: &str, generated: &str) -> String {
+    let diff = TextDiff::from_lines(existing, generated);
+    let old_lines = diff.old_slices();
+    let new_lines = diff.new_slices();
+    let mut merged = String::new();
+    let mut push_lines = |lines: &[&str]| {
+        for line in lines {
+            merged.push_str(line);
+            if !line.ends_with('\n') {
+                merged.push('\n');
+            }
+        }
+    };
+    for op in diff.ops() {
+        match *op {
+            DiffOp::Equal { old_index, len, .. } => {
+                push_lines(&old_lines[old_index..old_index + len])
+            }
+            _ => {
+                let (old_range, new_range) = (op.old_range(), op.new_range());
+                push_lines(&["<<<<<<< existing"]);
+                push_lines(&old_lines[old_range]);
+                push_lines(&["======="]);
+                push_lines(&new_lines[new_range]);
+                push_lines(&[">>>>>>> generated"]);
+            }
+        }
+    }
+
+    merged
+}
+
+/// Path of the generated file written next to the existing one
+pub(crate) fn new_file_path(path: &Path) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(NEW_FILE_SUFFIX);
+    path.into()
+}
+
+/// Files of the existing project which are not the generated ones, displayed at the end
+#[derive(Default)]
+pub(crate) struct ConflictSummary {
+    pub(crate) kept: Vec<PathBuf>,
+    pub(crate) renamed: Vec<PathBuf>,
+    pub(crate) merged: Vec<PathBuf>,
+}
+
+impl ConflictSummary {
+    pub(crate) fn print(&self) {
+        let yellow = Style::new().yellow();
+        let sections = [
+            (
+                &self.kept,
+                "Existing files kept, the generated ones were skipped:",
+            ),
+            (
+                &self.renamed,
+                "Generated files written alongside the existing ones:",
+            ),
+            (
+                &self.merged,
+                "Files merged, resolve their conflict markers:",
+            ),
+        ];
+        for (paths, title) in sections {
+            if paths.is_empty() {
+                continue;
+            }
+            println!("{} {}", Emoji("⚠️", ""), yellow.apply_to(title));
+            for path in paths {
+                println!("   - {}", path.to_string_lossy());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn policies() {
+        let path = Path::new("src/main.rs");
+        let resolve = |policy| resolve(path, b"old", b"new", Some(policy));
+        assert_eq!(resolve(ConflictPolicy::Skip).unwrap(), Resolution::Keep);
+        assert_eq!(
+            resolve(ConflictPolicy::Overwrite).unwrap(),
+            Resolution::Overwrite
+        );
+        assert_eq!(resolve(ConflictPolicy::Rename).unwrap(), Resolution::Rename);
+        assert_eq!(
+            resolve(ConflictPolicy::Fail).unwrap_err().to_string(),
+            "cannot generate src/main.rs because it already exists"
+        );
+        assert_eq!(
+            new_file_path(path),
+            PathBuf::from("src/main.rs.scaffold-new")
+        );
+    }
+
+    #[test]
+    fn conflict_markers() {
+        let existing = "[package]\nname = \"service\"\n\n[dependencies]\ntokio = \"1\"";
+        let generated = "[package]\nname = \"service\"\n\n[dependencies]\naxum = \"0.7\"\n";
+        assert_eq!(
+            merge_with_markers(existing, generated),
+            "[package]\nname = \"service\"\n\n[dependencies]\n\
+             <<<<<<< existing\ntokio = \"1\"\n=======\naxum = \"0.7\"\n>>>>>>> generated\n"
+        );
+        assert_eq!(merge_with_markers("same\n", "same\n"), "same\n");
+    }
+}
@@ -1,13 +1,94 @@
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use console::{Emoji, Style};
-use std::path::Path;
+use semver::{Version, VersionReq};
+use std::{
+    fs::{self, File},
+    io::{Read, Write},
+    path::{Component, Path, PathBuf},
+    process::{Command, Stdio},
+};
+use walkdir::WalkDir;
+
+const LFS_POINTER_HEADER: &[u8] = b"version https://git-lfs.github.com/spec/v1\n";
+// Pointer files are always smaller than this (https://github.com/git-lfs/git-lfs/blob/main/docs/spec.md)
+const LFS_POINTER_MAX_SIZE: u64 = 1024;
+
+/// Returns true if the git reference is a semver requirement (i.e.: "^1.2", "~2", "latest")
+/// rather than an exact commit, tag or branch. A branch or tag named "latest" is used as is,
+/// see `resolve_semver_tag`.
+pub(crate) fn is_semver_requirement(reference: &str) -> bool {
+    reference == "latest"
+        || reference.starts_with(['^', '~', '=', '>', '<', '*'])
+            && VersionReq::parse(reference).is_ok()
+}
+
+/// List the tags of the remote repository and return the highest one (and the commit it points to)
+/// matching the semver requirement. Tags may be prefixed by a `v` (i.e.: "v1.2.3").
+/// Returns None for "latest" when the repository has a branch or a tag with this name.
+fn resolve_semver_tag(
+    repository: &str,
+    requirement: &str,
+    callbacks: git2::RemoteCallbacks,
+) -> Result<Option<(String, git2::Oid)>> {
+    let version_req = match requirement {
+        "latest" => VersionReq::STAR,
+        requirement => VersionReq::parse(requirement)
+            .with_context(|| format!("invalid semver requirement {requirement:?}"))?,
+    };
+
+    let mut remote = git2::Remote::create_detached(repository)?;
+    let connection = remote
+        .connect_auth(git2::Direction::Fetch, Some(callbacks), None)
+        .map_err(|e| fetch_error(repository, e))?;
+
+    let heads = connection.list()?;
+    let is_latest_ref = heads
+        .iter()
+        .any(|head| matches!(head.name(), "refs/heads/latest" | "refs/tags/latest"));
+    if requirement == "latest" && is_latest_ref {
+        return Ok(None);
+    }
+
+    let mut best: Option<(Version, String, git2::Oid)> = None;
+    for head in heads {
+        let Some(tag) = head.name().strip_prefix("refs/tags/") else {
+            continue;
+        };
+        // Annotated tags are listed twice, the peeled entry points to the commit
+        let (tag, peeled) = match tag.strip_suffix("^{}") {
+            Some(tag) => (tag, true),
+            None => (tag, false),
+        };
+        let Ok(version) = Version::parse(tag.trim_start_matches('v')) else {
+            continue;
+        };
+        if !version_req.matches(&version) {
+            continue;
+        }
+        match &mut best {
+            Some((best_version, best_tag, oid)) if *best_version == version && best_tag == tag => {
+                if peeled {
+                    *oid = head.oid();
+                }
+            }
+            Some((best_version, ..)) if *best_version >= version => {}
+            _ => best = Some((version, tag.to_string(), head.oid())),
+        }
+    }
+
+    best.map(|(_, tag, oid)| Some((tag, oid)))
+        .ok_or_else(|| anyhow!("no tag of {repository} matches the requirement {requirement:?}"))
+}
 
 pub(crate) fn clone(
     repository: &str,
     reference_opt: Option<&str>,
     target_dir: &Path,
     private_key_path: Option<&Path>,
+    submodules: bool,
+    subpath: Option<&Path>,
 ) -> Result<()> {
+    let subpath = subpath.map(normalize_subpath).transpose()?;
     let cyan = Style::new().cyan();
     println!(
         "{} {}",
@@ -23,61 +104,950 @@ pub(crate) fn clone(
     let git_config = git2::Config::open_default()
         .map_err(|e| anyhow!(e).context("Opening git configuration"))?;
 
-    let mut fetch_options = git2::FetchOptions::new();
+    let resolved_tag;
+    let reference_opt = match reference_opt {
+        Some(requirement) if is_semver_requirement(requirement) => {
+            let mut callbacks = git2::RemoteCallbacks::new();
+            callbacks.credentials(auth.credentials(&git_config));
+            match resolve_semver_tag(repository, requirement, callbacks)? {
+                Some((tag, commit)) => {
+                    println!(
+                        "{} {}",
+                        Emoji("🏷️ ", ""),
+                        cyan.apply_to(format!(
+                            "Resolved {requirement} to tag {tag} (commit {commit})"
+                        )),
+                    );
+                    resolved_tag = tag;
+                    Some(resolved_tag.as_str())
+                }
+                None => Some(requirement),
+            }
+        }
+        reference_opt => reference_opt,
+    };
 
-    // Add credentials callback.
-    let mut callbacks = git2::RemoteCallbacks::new();
-    callbacks.credentials(auth.credentials(&git_config));
-    fetch_options.remote_callbacks(callbacks);
+    let callbacks = || {
+        let mut callbacks = git2::RemoteCallbacks::new();
+        callbacks.credentials(auth.credentials(&git_config));
+        callbacks
+    };
 
-    if reference_opt.is_some() {
-        fetch_options.download_tags(git2::AutotagOption::All);
-    }
+    let repo = git2::Repository::init(target_dir)?;
+    let mut remote = repo.remote("origin", repository)?;
 
-    // we don't need to download the entire history
-    fetch_options.depth(1);
+    // List the remote references first so we only fetch what is needed
+    let (heads, default_branch) = {
+        let mut connection = remote
+            .connect_auth(git2::Direction::Fetch, Some(callbacks()), None)
+            .map_err(|e| fetch_error(repository, e))?;
+        let heads = connection
+            .list()?
+            .iter()
+            .map(|head| head.name().to_string())
+            .collect::<Vec<_>>();
+        let default_branch = connection
+            .remote()
+            .default_branch()
+            .ok()
+            .and_then(|branch| branch.as_str().map(String::from));
+        (heads, default_branch)
+    };
 
-    // Prepare builder.
-    let mut builder = git2::build::RepoBuilder::new();
-    builder.fetch_options(fetch_options);
+    let refname = match reference_opt {
+        None => Some(
+            default_branch
+                .ok_or_else(|| anyhow!("cannot find the default branch of {repository}"))?,
+        ),
+        Some(reference) => [
+            format!("refs/heads/{reference}"),
+            format!("refs/tags/{reference}"),
+            reference.to_string(),
+        ]
+        .into_iter()
+        .find(|refname| heads.contains(refname)),
+    };
 
-    // Clone the project.
-    let repo = builder.clone(repository, target_dir)?;
-
-    // Either a git tag, commit
-    if let Some(git_reference) = reference_opt {
-        match repo.revparse_ext(git_reference) {
-            Ok((obj, reference)) => {
-                repo.checkout_tree(&obj, None)?;
-                match reference {
-                    // tagref is an actual reference like branches or tags
-                    Some(reporef) => repo.set_head(reporef.name().expect("tag has a name; qed")),
-                    // this is a commit, not a reference
-                    None => repo.set_head_detached(obj.id()),
-                }?;
+    let commit = match &refname {
+        Some(refname) => {
+            let local_refname = match refname.strip_prefix("refs/heads/") {
+                Some(branch) => format!("refs/remotes/origin/{branch}"),
+                None => refname.clone(),
+            };
+            // we don't need to download the entire history
+            fetch(
+                &mut remote,
+                &[format!("+{refname}:{local_refname}")],
+                1,
+                callbacks(),
+            )
+            .map_err(|e| fetch_error(repository, e))?;
+            repo.find_reference(&local_refname)?.peel_to_commit()?
+        }
+        None => {
+            let reference = reference_opt.expect("the default branch is always found; qed");
+            if !is_commit_hash(reference) {
+                return Err(anyhow!(
+                    "cannot find reference {reference:?} in {repository}: it is neither a branch, a tag nor a commit"
+                ));
             }
-            Err(_) => {
-                // It might be a branch
-                std::fs::remove_dir_all(target_dir)?;
-                builder.branch(git_reference);
-                let _repo = builder.clone(repository, target_dir)?;
+            fetch_commit(&repo, &mut remote, reference, callbacks).map_err(|e| match e
+                .downcast::<git2::Error>()
+            {
+                Ok(e) => fetch_error(repository, e),
+                Err(e) => e,
+            })?
+        }
+    };
+
+    // Only materialize the selected template when it's located in a subdirectory.
+    // Filtered fetches (partial clones) are not supported by libgit2, so the objects of the
+    // whole commit are still downloaded but only the subtree is checked out.
+    let mut checkout = git2::build::CheckoutBuilder::new();
+    checkout.force();
+    if let Some(subpath) = &subpath {
+        check_template_subpath(&commit.tree()?, subpath, repository)?;
+        checkout.path(subpath);
+    }
+    repo.checkout_tree(commit.as_object(), Some(&mut checkout))?;
+    match refname
+        .as_deref()
+        .and_then(|r| r.strip_prefix("refs/heads/"))
+    {
+        Some(branch) => {
+            repo.branch(branch, &commit, true)?;
+            repo.set_head(&format!("refs/heads/{branch}"))?;
+        }
+        // tags and commits are checked out on a detached HEAD
+        None => repo.set_head_detached(commit.id())?,
+    }
+
+    let subpath = subpath.unwrap_or_default();
+    if submodules {
+        update_submodules(&repo, &subpath, &callbacks)?;
+    }
+    smudge_lfs_files(&target_dir.join(&subpath))?;
+
+    Ok(())
+}
+
+/// Check out another template of a repository cloned by `clone`, at the same commit. Its path
+/// is relative to the repository root and may go up (i.e.: `service/../base`), but not outside
+/// of the repository. Returns the path of the template relative to the repository root.
+pub(crate) fn checkout_template(
+    repository_dir: &Path,
+    subpath: &Path,
+    private_key_path: Option<&Path>,
+    submodules: bool,
+) -> Result<PathBuf> {
+    let mut resolved = PathBuf::new();
+    for component in subpath.components() {
+        match component {
+            Component::Normal(part) => resolved.push(part),
+            Component::CurDir => {}
+            Component::ParentDir if resolved.pop() => {}
+            _ => {
+                return Err(anyhow!(
+                    "invalid template path {subpath:?}: it is outside of the repository"
+                ))
             }
         }
     }
 
+    let repo = git2::Repository::open(repository_dir)?;
+    let commit = repo.head()?.peel_to_commit()?;
+    let repository = repo
+        .find_remote("origin")?
+        .url()
+        .unwrap_or_default()
+        .to_string();
+    check_template_subpath(&commit.tree()?, &resolved, &repository)?;
+    let mut checkout = git2::build::CheckoutBuilder::new();
+    checkout.force().path(&resolved);
+    repo.checkout_tree(commit.as_object(), Some(&mut checkout))?;
+
+    if submodules {
+        let mut auth = auth_git2::GitAuthenticator::default();
+        if let Some(private_key_path) = private_key_path {
+            auth = auth.add_ssh_key_from_file(private_key_path, None)
+        }
+        let git_config = git2::Config::open_default()
+            .map_err(|e| anyhow!(e).context("Opening git configuration"))?;
+        let callbacks = || {
+            let mut callbacks = git2::RemoteCallbacks::new();
+            callbacks.credentials(auth.credentials(&git_config));
+            callbacks
+        };
+        update_submodules(&repo, &resolved, &callbacks)?;
+    }
+    smudge_lfs_files(&repository_dir.join(&resolved))?;
+
+    Ok(resolved)
+}
+
+/// Path of the template relative to the repository root, without its `.` components
+fn normalize_subpath(subpath: &Path) -> Result<PathBuf> {
+    subpath
+        .components()
+        .filter(|c| *c != Component::CurDir)
+        .map(|c| match c {
+            Component::Normal(part) => Ok(part),
+            _ => Err(anyhow!(
+                "invalid template path {subpath:?}: it must be relative to the repository root, without `..`"
+            )),
+        })
+        .collect()
+}
+
+/// Make sure the subpath of the repository is a template directory, listing the available ones otherwise
+fn check_template_subpath(tree: &git2::Tree, subpath: &Path, repository: &str) -> Result<()> {
+    let is_template = tree
+        .get_path(&subpath.join(crate::SCAFFOLD_FILENAME))
+        .is_ok_and(|entry| entry.kind() == Some(git2::ObjectType::Blob));
+    if is_template {
+        return Ok(());
+    }
+
+    let templates = template_directories(tree)?;
+    let reason = match tree.get_path(subpath) {
+        Ok(_) => format!("it doesn't contain a {}", crate::SCAFFOLD_FILENAME),
+        Err(_) => "it doesn't exist".to_string(),
+    };
+    if templates.is_empty() {
+        Err(anyhow!(
+            "{subpath:?} is not a template directory of {repository}: {reason}, and the repository doesn't contain any template"
+        ))
+    } else {
+        Err(anyhow!(
+            "{subpath:?} is not a template directory of {repository}: {reason}. Available templates are: {}",
+            templates.join(", ")
+        ))
+    }
+}
+
+/// List the directories of the tree containing a scaffold file
+fn template_directories(tree: &git2::Tree) -> Result<Vec<String>> {
+    let mut templates = Vec::new();
+    tree.walk(git2::TreeWalkMode::PreOrder, |root, entry| {
+        if entry.name() == Some(crate::SCAFFOLD_FILENAME) {
+            let dir = root.trim_end_matches('/');
+            templates.push(if dir.is_empty() { "." } else { dir }.to_string());
+        }
+        git2::TreeWalkResult::Ok
+    })?;
+
+    Ok(templates)
+}
+
+/// Recursively init and checkout the submodules located in `subpath`, fetching them with the same credentials
+fn update_submodules<'a>(
+    repo: &git2::Repository,
+    subpath: &Path,
+    callbacks: &impl Fn() -> git2::RemoteCallbacks<'a>,
+) -> Result<()> {
+    for mut submodule in repo.submodules()? {
+        // Submodules outside of the checked out template are not needed
+        if !submodule.path().starts_with(subpath) {
+            continue;
+        }
+        let name = submodule.name().unwrap_or_default().to_string();
+        println!(
+            "{} {}",
+            Emoji("🔄", ""),
+            Style::new()
+                .cyan()
+                .apply_to(format!("Checking out submodule {name}…")),
+        );
+
+        let mut fetch_options = git2::FetchOptions::new();
+        fetch_options.remote_callbacks(callbacks());
+        let mut update_options = git2::SubmoduleUpdateOptions::new();
+        update_options.fetch(fetch_options);
+        submodule
+            .update(true, Some(&mut update_options))
+            .map_err(|e| {
+                let url = submodule.url().unwrap_or_default().to_string();
+                fetch_error(&url, e).context(format!("Cannot checkout submodule {name}"))
+            })?;
+
+        update_submodules(&submodule.open()?, Path::new(""), callbacks)?;
+    }
+
+    Ok(())
+}
+
+/// Returns true if the file is a Git LFS pointer instead of the actual content
+fn is_lfs_pointer(path: &Path) -> Result<bool> {
+    if fs::metadata(path)?.len() > LFS_POINTER_MAX_SIZE {
+        return Ok(false);
+    }
+    let mut header = Vec::new();
+    File::open(path)?
+        .take(LFS_POINTER_HEADER.len() as u64)
+        .read_to_end(&mut header)?;
+    Ok(header == LFS_POINTER_HEADER)
+}
+
+fn find_lfs_pointers(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut pointers = Vec::new();
+    let entries = WalkDir::new(dir)
+        .into_iter()
+        .filter_entry(|entry| entry.file_name() != ".git");
+    for entry in entries {
+        let entry = entry?;
+        if entry.file_type().is_file() && is_lfs_pointer(entry.path())? {
+            pointers.push(entry.into_path());
+        }
+    }
+
+    Ok(pointers)
+}
+
+/// Replace the Git LFS pointer files by their content using the local `git-lfs` binary
+fn smudge_lfs_files(dir: &Path) -> Result<()> {
+    let pointers = find_lfs_pointers(dir)?;
+    if pointers.is_empty() {
+        return Ok(());
+    }
+
+    let lfs_available = Command::new("git")
+        .args(["lfs", "version"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success());
+    if !lfs_available {
+        return Err(anyhow!(
+            "the template contains {} Git LFS pointer file(s) (i.e.: {:?}) but git-lfs is not installed, install it from https://git-lfs.com to fetch their content",
+            pointers.len(),
+            pointers[0].strip_prefix(dir).unwrap_or(&pointers[0])
+        ));
+    }
+
+    println!(
+        "{} {}",
+        Emoji("🔄", ""),
+        Style::new().cyan().apply_to("Fetching Git LFS files…"),
+    );
+    for pointer in pointers {
+        let relative_path = pointer.strip_prefix(dir).unwrap_or(&pointer);
+        // Submodules are repositories on their own, git-lfs must run from their directory
+        let repo_dir = git2::Repository::discover(&pointer)?
+            .workdir()
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| dir.to_path_buf());
+        let mut child = Command::new("git")
+            .args(["lfs", "smudge", "--"])
+            .arg(pointer.strip_prefix(&repo_dir).unwrap_or(&pointer))
+            .current_dir(&repo_dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .context("cannot execute git-lfs")?;
+        child
+            .stdin
+            .take()
+            .expect("stdin is piped; qed")
+            .write_all(&fs::read(&pointer)?)?;
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "git-lfs cannot fetch the content of {relative_path:?}"
+            ));
+        }
+        fs::write(&pointer, output.stdout)?;
+    }
+
     Ok(())
 }
 
+fn fetch(
+    remote: &mut git2::Remote,
+    refspecs: &[String],
+    depth: i32,
+    callbacks: git2::RemoteCallbacks,
+) -> Result<(), git2::Error> {
+    let mut fetch_options = git2::FetchOptions::new();
+    fetch_options.remote_callbacks(callbacks);
+    fetch_options.download_tags(git2::AutotagOption::None);
+    fetch_options.depth(depth);
+    remote.fetch(refspecs, Some(&mut fetch_options), None)
+}
+
+/// Fetch a commit which is not pointed by any remote reference.
+///
+/// A full hash is first fetched directly, which works when the server allows fetching any reachable commit.
+/// Otherwise the history of every branch and tag is deepened until the commit is found.
+fn fetch_commit<'r, 'a>(
+    repo: &'r git2::Repository,
+    remote: &mut git2::Remote,
+    reference: &str,
+    callbacks: impl Fn() -> git2::RemoteCallbacks<'a>,
+) -> Result<git2::Commit<'r>> {
+    if reference.len() == 40 && fetch(remote, &[reference.to_string()], 1, callbacks()).is_ok() {
+        if let Ok(commit) = repo.find_commit(git2::Oid::from_str(reference)?) {
+            return Ok(commit);
+        }
+    }
+
+    let refspecs = [
+        "+refs/heads/*:refs/remotes/origin/*".to_string(),
+        "+refs/tags/*:refs/tags/*".to_string(),
+    ];
+    // i32::MAX asks the server for the complete history
+    for depth in [50, 500, i32::MAX] {
+        fetch(remote, &refspecs, depth, callbacks())?;
+        if let Ok(commit) = repo
+            .revparse_single(reference)
+            .and_then(|object| object.peel_to_commit())
+        {
+            return Ok(commit);
+        }
+    }
+
+    Err(anyhow!(
+        "cannot find reference {reference:?} in {}: it is neither a branch, a tag nor a commit",
+        remote.url().unwrap_or_default()
+    ))
+}
+
+fn is_commit_hash(reference: &str) -> bool {
+    (4..=40).contains(&reference.len()) && reference.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Distinguish authentication and network failures from other git errors
+fn fetch_error(repository: &str, error: git2::Error) -> anyhow::Error {
+    let context = match (error.code(), error.class()) {
+        (git2::ErrorCode::Auth, _) | (_, git2::ErrorClass::Ssh) => {
+            format!("Authentication failed for {repository}")
+        }
+        (_, git2::ErrorClass::Net | git2::ErrorClass::Http | git2::ErrorClass::Ssl) => {
+            format!("Cannot reach {repository}")
+        }
+        _ => format!("Cannot fetch {repository}"),
+    };
+    anyhow!(error).context(context)
+}
+
+/// Returns true if the directory is in a git repository with modified or untracked files under
+/// it, which would be lost by replacing it
+pub(crate) fn has_uncommitted_changes(dir: &Path) -> Result<bool> {
+    let repo = match git2::Repository::discover(dir) {
+        Ok(repo) => repo,
+        Err(e) if e.code() == git2::ErrorCode::NotFound => return Ok(false),
+        Err(e) => return Err(anyhow!(e).context(format!("Cannot open repository {dir:?}"))),
+    };
+    let Some(workdir) = repo.workdir() else {
+        return Ok(false);
+    };
+    let mut options = git2::StatusOptions::new();
+    options
+        .include_untracked(true)
+        .include_ignored(false)
+        .disable_pathspec_match(true);
+    // only the changes under the directory are lost, not the ones elsewhere in the repository
+    let dir = dir
+        .canonicalize()
+        .with_context(|| format!("Cannot resolve path {dir:?}"))?;
+    let workdir = workdir
+        .canonicalize()
+        .with_context(|| format!("Cannot resolve path {workdir:?}"))?;
+    if let Ok(relative) = dir.strip_prefix(&workdir) {
+        if !relative.as_os_str().is_empty() {
+            options.pathspec(relative);
+        }
+    }
+    let statuses = repo
+        .statuses(Some(&mut options))
+        .with_context(|| format!("Cannot get the status of repository {workdir:?}"))?;
+
+    Ok(!statuses.is_empty())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tempfile::tempdir;
+    use std::fs;
+    use tempfile::{tempdir, TempDir};
+
+    /// Create a local repository with one commit per tag, each commit writing the tag in `VERSION`
+    fn fixture_repo(tags: &[&str]) -> TempDir {
+        let dir = tempdir().unwrap();
+        let repo = git2::Repository::init(dir.path()).unwrap();
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+        let mut parent: Option<git2::Commit> = None;
+        for tag in tags {
+            fs::write(dir.path().join("VERSION"), tag).unwrap();
+            let mut index = repo.index().unwrap();
+            index.add_path(Path::new("VERSION")).unwrap();
+            index.write().unwrap();
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let parents = parent.iter().collect::<Vec<_>>();
+            let oid = repo
+                .commit(Some("HEAD"), &signature, &signature, tag, &tree, &parents)
+                .unwrap();
+            let commit = repo.find_commit(oid).unwrap();
+            repo.tag(tag, commit.as_object(), &signature, tag, false)
+                .unwrap();
+            parent = Some(commit);
+        }
+        dir
+    }
+
+    /// Create a local repository with a single commit containing the given files
+    fn fixture_repo_with_files(files: &[(&str, &str)]) -> TempDir {
+        let dir = tempdir().unwrap();
+        let repo = git2::Repository::init(dir.path()).unwrap();
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+        let mut index = repo.index().unwrap();
+        for (path, content) in files {
+            let full_path = dir.path().join(path);
+            fs::create_dir_all(full_path.parent().unwrap()).unwrap();
+            fs::write(full_path, content).unwrap();
+            index.add_path(Path::new(path)).unwrap();
+        }
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        repo.commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])
+            .unwrap();
+        dir
+    }
+
+    fn file_url(dir: &TempDir) -> String {
+        format!("file://{}", dir.path().display())
+    }
+
+    #[test]
+    fn semver_requirements() {
+        assert!(is_semver_requirement("^1.2"));
+        assert!(is_semver_requirement("~2"));
+        assert!(is_semver_requirement(">=1.0, <2.0"));
+        assert!(is_semver_requirement("latest"));
+        assert!(!is_semver_requirement("v1.2.3"));
+        assert!(!is_semver_requirement("1.2.3"));
+        assert!(!is_semver_requirement("main"));
+        assert!(!is_semver_requirement(
+            "8f0039488b3877ca59592900bc7ad645a83e2886"
+        ));
+    }
+
+    #[test]
+    fn resolve_semver_tags() {
+        let origin = fixture_repo(&[
+            "v1.1.0",
+            "v1.2.0",
+            "v1.2.5",
+            "v2.0.0-beta.1",
+            "v2.0.0",
+            "v3.0.0-rc.1",
+        ]);
+        let url = file_url(&origin);
+        let resolve = |req| {
+            resolve_semver_tag(&url, req, git2::RemoteCallbacks::new())
+                .map(|resolved| resolved.unwrap().0)
+        };
+
+        assert_eq!(resolve("^1.2").unwrap(), "v1.2.5");
+        assert_eq!(resolve("~1.1").unwrap(), "v1.1.0");
+        assert_eq!(resolve("^2").unwrap(), "v2.0.0");
+        assert_eq!(resolve("latest").unwrap(), "v2.0.0");
+        assert!(resolve("^4").is_err());
+
+        let (tag, commit) = resolve_semver_tag(&url, "~1.2.0", git2::RemoteCallbacks::new())
+            .unwrap()
+            .unwrap();
+        let repo = git2::Repository::open(origin.path()).unwrap();
+        let expected = repo
+            .revparse_single(&tag)
+            .unwrap()
+            .peel_to_commit()
+            .unwrap()
+            .id();
+        assert_eq!(commit, expected);
+    }
+
+    #[test]
+    fn latest_ref_is_not_a_requirement() {
+        let origin = fixture_repo(&["v1.0.0", "latest", "v2.0.0"]);
+        let resolved =
+            resolve_semver_tag(&file_url(&origin), "latest", git2::RemoteCallbacks::new()).unwrap();
+        assert_eq!(resolved, None);
+
+        let tmp_dir = tempdir().unwrap();
+        clone(
+            &file_url(&origin),
+            Some("latest"),
+            tmp_dir.path(),
+            None,
+            false,
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            fs::read_to_string(tmp_dir.path().join("VERSION")).unwrap(),
+            "latest"
+        );
+    }
+
+    #[test]
+    fn clone_semver_tag() {
+        let origin = fixture_repo(&["v1.0.0", "v1.4.2", "v2.0.0"]);
+        let tmp_dir = tempdir().unwrap();
+        clone(
+            &file_url(&origin),
+            Some("^1"),
+            tmp_dir.path(),
+            None,
+            false,
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            fs::read_to_string(tmp_dir.path().join("VERSION")).unwrap(),
+            "v1.4.2"
+        );
+    }
+
+    fn commit_of(origin: &TempDir, reference: &str) -> String {
+        let repo = git2::Repository::open(origin.path()).unwrap();
+        let commit = repo
+            .revparse_single(reference)
+            .unwrap()
+            .peel_to_commit()
+            .unwrap();
+        commit.id().to_string()
+    }
+
+    fn cloned_version(dir: &TempDir) -> String {
+        fs::read_to_string(dir.path().join("VERSION")).unwrap()
+    }
+
+    #[test]
+    fn uncommitted_changes() {
+        let repo = fixture_repo(&["v1.0.0"]);
+        assert!(!has_uncommitted_changes(repo.path()).unwrap());
+        fs::write(repo.path().join(".env"), "SECRET=1").unwrap();
+        assert!(has_uncommitted_changes(repo.path()).unwrap());
+
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join(".env"), "SECRET=1").unwrap();
+        assert!(!has_uncommitted_changes(dir.path()).unwrap());
+    }
+
+    #[test]
+    fn uncommitted_changes_in_a_subdirectory() {
+        let repo = fixture_repo_with_files(&[("project/a.txt", "a"), ("other/b.txt", "b")]);
+        let project = repo.path().join("project");
+        fs::write(repo.path().join("other/b.txt"), "changed").unwrap();
+        fs::write(repo.path().join("other/c.txt"), "new").unwrap();
+        assert!(!has_uncommitted_changes(&project).unwrap());
+
+        fs::write(project.join("a.txt"), "changed").unwrap();
+        assert!(has_uncommitted_changes(&project).unwrap());
+        fs::write(project.join("a.txt"), "a").unwrap();
+        fs::create_dir(project.join("new")).unwrap();
+        fs::write(project.join("new/d.txt"), "new").unwrap();
+        assert!(has_uncommitted_changes(&project).unwrap());
+    }
+
+    #[test]
+    fn clone_local_default_branch() {
+        let origin = fixture_repo(&["v1.0.0", "v2.0.0"]);
+        let tmp_dir = tempdir().unwrap();
+        clone(&file_url(&origin), None, tmp_dir.path(), None, false, None).unwrap();
+        assert_eq!(cloned_version(&tmp_dir), "v2.0.0");
+        let repo = git2::Repository::open(tmp_dir.path()).unwrap();
+        assert!(repo.head().unwrap().is_branch());
+    }
+
+    #[test]
+    fn clone_local_tag() {
+        let origin = fixture_repo(&["v1.0.0", "v2.0.0"]);
+        let tmp_dir = tempdir().unwrap();
+        clone(
+            &file_url(&origin),
+            Some("v1.0.0"),
+            tmp_dir.path(),
+            None,
+            false,
+            None,
+        )
+        .unwrap();
+        assert_eq!(cloned_version(&tmp_dir), "v1.0.0");
+    }
+
+    #[test]
+    fn clone_local_branch() {
+        let origin = fixture_repo(&["v1.0.0", "v2.0.0"]);
+        {
+            let repo = git2::Repository::open(origin.path()).unwrap();
+            let commit = repo.find_commit(commit_of(&origin, "v1.0.0").parse().unwrap());
+            repo.branch("feature", &commit.unwrap(), false).unwrap();
+        }
+        let tmp_dir = tempdir().unwrap();
+        clone(
+            &file_url(&origin),
+            Some("feature"),
+            tmp_dir.path(),
+            None,
+            false,
+            None,
+        )
+        .unwrap();
+        assert_eq!(cloned_version(&tmp_dir), "v1.0.0");
+        let repo = git2::Repository::open(tmp_dir.path()).unwrap();
+        assert_eq!(repo.head().unwrap().shorthand(), Some("feature"));
+    }
+
+    #[test]
+    fn clone_local_commit() {
+        let origin = fixture_repo(&["v1.0.0", "v2.0.0", "v3.0.0"]);
+        let commit = commit_of(&origin, "v1.0.0");
+
+        let tmp_dir = tempdir().unwrap();
+        clone(
+            &file_url(&origin),
+            Some(&commit),
+            tmp_dir.path(),
+            None,
+            false,
+            None,
+        )
+        .unwrap();
+        assert_eq!(cloned_version(&tmp_dir), "v1.0.0");
+
+        let tmp_dir = tempdir().unwrap();
+        clone(
+            &file_url(&origin),
+            Some(&commit[..8]),
+            tmp_dir.path(),
+            None,
+            false,
+            None,
+        )
+        .unwrap();
+        assert_eq!(cloned_version(&tmp_dir), "v1.0.0");
+    }
+
+    #[test]
+    fn clone_local_missing_reference() {
+        let origin = fixture_repo(&["v1.0.0"]);
+
+        let tmp_dir = tempdir().unwrap();
+        let err = clone(
+            &file_url(&origin),
+            Some("unknown"),
+            tmp_dir.path(),
+            None,
+            false,
+            None,
+        )
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("cannot find reference \"unknown\""));
+
+        let tmp_dir = tempdir().unwrap();
+        let missing_commit = "0123456789abcdef0123456789abcdef01234567";
+        let err = clone(
+            &file_url(&origin),
+            Some(missing_commit),
+            tmp_dir.path(),
+            None,
+            false,
+            None,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("cannot find reference"));
+    }
+
+    #[test]
+    fn clone_unreachable_repository() {
+        let missing = tempdir().unwrap();
+        let url = format!("file://{}", missing.path().join("missing").display());
+        let tmp_dir = tempdir().unwrap();
+        let err = clone(&url, Some("main"), tmp_dir.path(), None, false, None).unwrap_err();
+        assert!(err.to_string().starts_with("Cannot "));
+        assert!(!err.to_string().contains("cannot find reference"));
+    }
+
+    #[test]
+    fn clone_local_submodules() {
+        let shared = fixture_repo(&["v1.0.0"]);
+        let origin = fixture_repo(&["v2.0.0"]);
+        {
+            let repo = git2::Repository::open(origin.path()).unwrap();
+            let mut submodule = repo
+                .submodule(&file_url(&shared), Path::new("shared"), true)
+                .unwrap();
+            submodule.clone(None).unwrap();
+            submodule.add_finalize().unwrap();
+            let signature = git2::Signature::now("test", "test@example.com").unwrap();
+            let tree = repo
+                .find_tree(repo.index().unwrap().write_tree().unwrap())
+                .unwrap();
+            let parent = repo.head().unwrap().peel_to_commit().unwrap();
+            repo.commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                "add submodule",
+                &tree,
+                &[&parent],
+            )
+            .unwrap();
+        }
+
+        let tmp_dir = tempdir().unwrap();
+        clone(&file_url(&origin), None, tmp_dir.path(), None, false, None).unwrap();
+        assert!(!tmp_dir.path().join("shared").join("VERSION").exists());
+
+        let tmp_dir = tempdir().unwrap();
+        clone(&file_url(&origin), None, tmp_dir.path(), None, true, None).unwrap();
+        assert_eq!(
+            fs::read_to_string(tmp_dir.path().join("shared").join("VERSION")).unwrap(),
+            "v1.0.0"
+        );
+    }
+
+    #[test]
+    fn clone_local_template_subpath() {
+        let origin = fixture_repo_with_files(&[
+            ("README.md", "templates"),
+            ("service/.scaffold.toml", "[template]"),
+            ("service/src/main.rs", "fn main() {}"),
+            ("library/.scaffold.toml", "[template]"),
+            ("library/src/lib.rs", ""),
+            ("docs/index.md", ""),
+        ]);
+
+        let tmp_dir = tempdir().unwrap();
+        clone(
+            &file_url(&origin),
+            None,
+            tmp_dir.path(),
+            None,
+            false,
+            Some(Path::new("./service")),
+        )
+        .unwrap();
+        assert!(tmp_dir.path().join("service/src/main.rs").exists());
+        assert!(!tmp_dir.path().join("library").exists());
+        assert!(!tmp_dir.path().join("README.md").exists());
+
+        let tmp_dir = tempdir().unwrap();
+        for subpath in ["../service", "service/../library", "/service"] {
+            let err = clone(
+                &file_url(&origin),
+                None,
+                tmp_dir.path(),
+                None,
+                false,
+                Some(Path::new(subpath)),
+            )
+            .unwrap_err();
+            assert!(err.to_string().starts_with("invalid template path"));
+        }
+
+        let tmp_dir = tempdir().unwrap();
+        let err = clone(
+            &file_url(&origin),
+            None,
+            tmp_dir.path(),
+            None,
+            false,
+            Some(Path::new("docs")),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("doesn't contain a .scaffold.toml"));
+        assert!(err
+            .to_string()
+            .contains("Available templates are: library, service"));
+
+        let tmp_dir = tempdir().unwrap();
+        let err = clone(
+            &file_url(&origin),
+            None,
+            tmp_dir.path(),
+            None,
+            false,
+            Some(Path::new("cli")),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("doesn't exist"));
+    }
+
+    #[test]
+    fn checkout_other_template() {
+        let origin = fixture_repo_with_files(&[
+            ("templates/base/.scaffold.toml", "[template]"),
+            ("templates/base/LICENSE", "MIT"),
+            ("templates/service/.scaffold.toml", "[template]"),
+            ("docs/index.md", ""),
+        ]);
+
+        let tmp_dir = tempdir().unwrap();
+        clone(
+            &file_url(&origin),
+            None,
+            tmp_dir.path(),
+            None,
+            false,
+            Some(Path::new("templates/service")),
+        )
+        .unwrap();
+        assert!(!tmp_dir.path().join("templates/base").exists());
+
+        let subpath = checkout_template(
+            tmp_dir.path(),
+            Path::new("templates/service/../base"),
+            None,
+            false,
+        )
+        .unwrap();
+        assert_eq!(subpath, Path::new("templates/base"));
+        assert!(tmp_dir.path().join("templates/base/LICENSE").exists());
+        assert!(tmp_dir
+            .path()
+            .join("templates/service/.scaffold.toml")
+            .exists());
+        assert!(!tmp_dir.path().join("docs").exists());
+
+        let err = checkout_template(
+            tmp_dir.path(),
+            Path::new("templates/../../base"),
+            None,
+            false,
+        )
+        .unwrap_err();
+        assert!(err.to_string().ends_with("it is outside of the repository"));
+        let err = checkout_template(tmp_dir.path(), Path::new("docs"), None, false).unwrap_err();
+        assert!(err.to_string().contains("doesn't contain a .scaffold.toml"));
+    }
+
+    #[test]
+    fn detect_lfs_pointers() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("assets")).unwrap();
+        fs::write(
+            dir.path().join("assets").join("logo.png"),
+            "version https://git-lfs.github.com/spec/v1\noid sha256:4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393\nsize 12345\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("README.md"), "version 1").unwrap();
+
+        assert_eq!(
+            find_lfs_pointers(dir.path()).unwrap(),
+            vec![dir.path().join("assets").join("logo.png")]
+        );
+    }
 
     #[test]
     fn clone_http() {
         let template_path = "https://github.com/http-rs/surf.git";
         let tmp_dir = tempdir().unwrap();
-        clone(template_path, None, tmp_dir.path(), None).unwrap();
+        clone(template_path, None, tmp_dir.path(), None, false, None).unwrap();
     }
 
     #[test]
@@ -85,7 +1055,7 @@ mod tests {
         let commit = Some("8f0039488b3877ca59592900bc7ad645a83e2886");
         let template_path = "https://github.com/http-rs/surf.git";
         let tmp_dir = tempdir().unwrap();
-        clone(template_path, commit, tmp_dir.path(), None).unwrap();
+        clone(template_path, commit, tmp_dir.path(), None, false, None).unwrap();
     }
 
     #[test]
@@ -93,7 +1063,7 @@ mod tests {
         let branch = Some("main");
         let template_path = "https://github.com/apollographql/router.git";
         let tmp_dir = tempdir().unwrap();
-        clone(template_path, branch, tmp_dir.path(), None).unwrap();
+        clone(template_path, branch, tmp_dir.path(), None, false, None).unwrap();
     }
 
     #[test]
@@ -101,7 +1071,7 @@ mod tests {
     fn clone_ssh() {
         let template_path = "git@github.com:http-rs/surf.git";
         let tmp_dir = tempdir().unwrap();
-        clone(template_path, None, tmp_dir.path(), None).unwrap();
+        clone(template_path, None, tmp_dir.path(), None, false, None).unwrap();
     }
 
     #[test]
@@ -110,6 +1080,6 @@ mod tests {
         let commit = Some("8f0039488b3877ca59592900bc7ad645a83e2886");
         let template_path = "git@github.com:http-rs/surf.git";
         let tmp_dir = tempdir().unwrap();
-        clone(template_path, commit, tmp_dir.path(), None).unwrap();
+        clone(template_path, commit, tmp_dir.path(), None, false, None).unwrap();
     }
 }
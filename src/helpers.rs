@@ -71,3 +71,61 @@ impl HelperDef for ForRangHelper {
         }
     }
 }
+
+/// Rhai engine running the script helpers of the templates, sandboxed: scripts can neither
+/// import modules from the filesystem nor evaluate code, and the standard library of Rhai has no
+/// filesystem or network functions. Scripts looping forever fail instead of hanging.
+#[cfg(feature = "script_helpers")]
+pub(crate) fn script_engine() -> rhai::Engine {
+    let mut engine = rhai::Engine::new();
+    engine.set_module_resolver(rhai::module_resolvers::DummyModuleResolver::new());
+    engine.disable_symbol("eval");
+    engine.on_print(|_| {});
+    engine.on_debug(|_, _, _| {});
+    engine.set_max_operations(1_000_000);
+    engine.set_max_call_levels(64);
+    engine.set_max_string_size(1 << 20);
+    engine.set_max_array_size(10_000);
+    engine.set_max_map_size(10_000);
+
+    engine
+}
+
+#[cfg(all(test, feature = "script_helpers"))]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn sandboxed_script_helpers() {
+        let mut template_engine = Handlebars::new();
+        template_engine.set_engine(script_engine());
+        template_engine
+            .register_script_helper(
+                "slug",
+                r#"let slug = params[0].to_lower(); slug.replace(" ", "-"); slug"#,
+            )
+            .unwrap();
+        template_engine
+            .register_script_helper("forever", "loop {}")
+            .unwrap();
+        assert_eq!(
+            template_engine
+                .render_template("{{slug name}}", &json!({"name": "My Service"}))
+                .unwrap(),
+            "my-service"
+        );
+        assert!(template_engine.render_template("{{forever}}", &()).is_err());
+        template_engine
+            .register_script_helper("huge", "let items = []; items.pad(100000, 0); items.len()")
+            .unwrap();
+        assert!(template_engine.render_template("{{huge}}", &()).is_err());
+        assert!(template_engine
+            .register_script_helper("read", r#"import "/etc/passwd" as passwd;"#)
+            .is_ok());
+        assert!(template_engine.render_template("{{read}}", &()).is_err());
+        assert!(template_engine
+            .register_script_helper("eval", r#"eval("40 + 2")"#)
+            .is_err());
+    }
+}
@@ -0,0 +1,371 @@
+use anyhow::{anyhow, Context, Result};
+use console::{Emoji, Style};
+use serde::{Deserialize, Serialize};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
+
+use crate::{git, Opts, SCAFFOLD_FILENAME};
+
+pub const INDEX_FILENAME: &str = "scaffold-index.toml";
+
+/// A template referenced by a template index
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexEntry {
+    /// Name used to select the template with `cargo scaffold new <name>`
+    name: Option<String>,
+    description: Option<String>,
+    /// Template location, as given to `cargo scaffold <template>`
+    source: String,
+    /// Template location in the repository
+    path: Option<PathBuf>,
+    git_ref: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    author: Option<String>,
+    version: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum IndexFile {
+    Entries { templates: Vec<IndexEntry> },
+    Entry(IndexEntry),
+}
+
+/// TemplateIndex: a list of templates, to find them by name, description or tags.
+///
+/// The index is either a TOML file listing templates in `[[templates]]` tables, a directory or a
+/// git repository (ending with ".git") containing such files, or one file per template.
+///
+/// ```toml
+/// [[templates]]
+/// name = "service"
+/// description = "An HTTP service with its CI"
+/// source = "https://github.com/username/templates.git"
+/// path = "service"
+/// git_ref = "^1"
+/// tags = ["rust", "http"]
+/// ```
+#[derive(Debug, Default)]
+pub struct TemplateIndex {
+    entries: Vec<IndexEntry>,
+}
+
+impl IndexEntry {
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or_default()
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    fn matches(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+        self.name().to_lowercase().contains(&query)
+            || self
+                .description
+                .as_ref()
+                .is_some_and(|d| d.to_lowercase().contains(&query))
+            || self.tags.iter().any(|t| t.to_lowercase() == query)
+    }
+
+    /// Fill the missing metadata from the `[template]` table of a local template
+    fn fill_from_template(&mut self, base_dir: &Path) {
+        // Only local templates are read, relatively to the index file
+        let source = base_dir.join(&self.source);
+        if !source.is_dir() {
+            return;
+        }
+        let template_dir = match &self.path {
+            Some(path) => source.join(path),
+            None => source.clone(),
+        };
+        self.source = source.to_string_lossy().to_string();
+
+        let Ok(content) = fs::read_to_string(template_dir.join(SCAFFOLD_FILENAME)) else {
+            return;
+        };
+        let Ok(desc) = toml::from_str::<toml::Table>(&content) else {
+            return;
+        };
+        let Some(template) = desc.get("template").and_then(|t| t.as_table()) else {
+            return;
+        };
+        let field = |key: &str| template.get(key).and_then(|v| v.as_str()).map(String::from);
+        self.name = self.name.take().or_else(|| field("name"));
+        self.description = self.description.take().or_else(|| field("description"));
+        self.author = self.author.take().or_else(|| field("author"));
+        self.version = self.version.take().or_else(|| field("version"));
+    }
+}
+
+impl TemplateIndex {
+    /// Load the index from a file, a directory or a git repository.
+    ///
+    /// Defaults to `scaffold-index.toml` in the cargo home directory.
+    pub fn load(location: Option<&str>) -> Result<Self> {
+        let location = match location {
+            Some(location) => location.to_string(),
+            None => default_location()?.to_string_lossy().to_string(),
+        };
+
+        let path = if location.ends_with(".git") {
+            let tmp_dir = env::temp_dir().join(format!("{:x}", md5::compute(&location)));
+            if tmp_dir.exists() {
+                fs::remove_dir_all(&tmp_dir)?;
+            }
+            fs::create_dir_all(&tmp_dir)?;
+            git::clone(&location, None, &tmp_dir, None, false, None)?;
+            tmp_dir
+        } else {
+            PathBuf::from(&location)
+        };
+
+        let mut index = TemplateIndex::default();
+        if path.is_dir() {
+            let files = WalkDir::new(&path)
+                .sort_by_file_name()
+                .into_iter()
+                // Hidden files and templates stored next to the index are not index files
+                .filter_entry(|entry| {
+                    entry.depth() == 0
+                        || !entry.file_name().to_string_lossy().starts_with('.')
+                            && !entry.path().join(SCAFFOLD_FILENAME).exists()
+                });
+            for file in files {
+                let file = file?;
+                if file.file_type().is_file()
+                    && file.path().extension().is_some_and(|ext| ext == "toml")
+                {
+                    index.load_file(file.path())?;
+                }
+            }
+        } else {
+            index
+                .load_file(&path)
+                .with_context(|| format!("cannot load template index {location}"))?;
+        }
+
+        Ok(index)
+    }
+
+    fn load_file(&mut self, path: &Path) -> Result<()> {
+        let content = fs::read_to_string(path).with_context(|| format!("cannot read {path:?}"))?;
+        let entries = match toml::from_str(&content)
+            .with_context(|| format!("invalid template index file {path:?}"))?
+        {
+            IndexFile::Entries { templates } => templates,
+            IndexFile::Entry(entry) => vec![entry],
+        };
+
+        let base_dir = path.parent().unwrap_or(Path::new("."));
+        for mut entry in entries {
+            entry.fill_from_template(base_dir);
+            if entry.name.is_none() {
+                return Err(anyhow!(
+                    "the template {} of the index {path:?} has no name",
+                    entry.source
+                ));
+            }
+            self.entries.push(entry);
+        }
+
+        Ok(())
+    }
+
+    pub fn entries(&self) -> &[IndexEntry] {
+        &self.entries
+    }
+
+    /// Find the templates whose name or description contains the query, or tagged by the query
+    pub fn search(&self, query: &str) -> Vec<&IndexEntry> {
+        self.entries.iter().filter(|e| e.matches(query)).collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&IndexEntry> {
+        self.entries.iter().find(|e| e.name() == name)
+    }
+
+    /// Replace the template name of the options by the location of the template from the index
+    pub fn resolve(&self, mut opts: Opts) -> Result<Opts> {
+        let name = opts.template_path.to_string_lossy().to_string();
+        let entry = self
+            .get(&name)
+            .ok_or_else(|| anyhow!("cannot find template {name:?} in the template index"))?;
+
+        opts.template_path = PathBuf::from(&entry.source);
+        if opts.repository_template_path.is_none() {
+            opts.repository_template_path = entry.path.clone();
+        }
+        if opts.git_ref.is_none() {
+            opts.git_ref = entry.git_ref.clone();
+        }
+
+        Ok(opts)
+    }
+
+    /// Display the templates
+    pub fn print(entries: &[&IndexEntry]) {
+        if entries.is_empty() {
+            println!("{} No template found", Emoji("🤷", ""));
+            return;
+        }
+
+        let green = Style::new().green();
+        let dim = Style::new().dim();
+        for entry in entries {
+            let version = entry
+                .version
+                .as_ref()
+                .map(|v| format!(" v{v}"))
+                .unwrap_or_default();
+            println!(
+                "{} {}{}",
+                Emoji("📦", "*"),
+                green.apply_to(entry.name()),
+                version
+            );
+            if let Some(description) = &entry.description {
+                println!("    {description}");
+            }
+            let location = match &entry.path {
+                Some(path) => format!("{} ({})", entry.source, path.to_string_lossy()),
+                None => entry.source.clone(),
+            };
+            println!("    {}", dim.apply_to(location));
+            if !entry.tags.is_empty() {
+                println!(
+                    "    {}",
+                    dim.apply_to(format!("tags: {}", entry.tags.join(", ")))
+                );
+            }
+        }
+    }
+}
+
+fn default_location() -> Result<PathBuf> {
+    let cargo_home = match env::var_os("CARGO_HOME") {
+        Some(cargo_home) => PathBuf::from(cargo_home),
+        None => PathBuf::from(
+            env::var_os("HOME").ok_or_else(|| anyhow!("cannot find the cargo home directory"))?,
+        )
+        .join(".cargo"),
+    };
+
+    Ok(cargo_home.join(INDEX_FILENAME))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn load_and_search_index() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join(INDEX_FILENAME),
+            r#"
+            [[templates]]
+            name = "service"
+            description = "An HTTP service"
+            source = "https://github.com/username/templates.git"
+            path = "service"
+            git_ref = "^1"
+            tags = ["rust", "http"]
+
+            [[templates]]
+            source = "./library"
+            tags = ["rust"]
+            "#,
+        )
+        .unwrap();
+        fs::create_dir(dir.path().join("library")).unwrap();
+        fs::write(
+            dir.path().join("library").join(SCAFFOLD_FILENAME),
+            "[template]\nname = \"library\"\nauthor = \"me\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+
+        let index = TemplateIndex::load(dir.path().join(INDEX_FILENAME).to_str()).unwrap();
+        assert_eq!(index.entries().len(), 2);
+        let library = index.get("library").unwrap();
+        assert_eq!(library.version.as_deref(), Some("0.1.0"));
+        assert_eq!(library.author.as_deref(), Some("me"));
+
+        let names = |entries: Vec<&IndexEntry>| {
+            entries
+                .iter()
+                .map(|e| e.name().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(index.search("rust")), vec!["service", "library"]);
+        assert_eq!(names(index.search("HTTP")), vec!["service"]);
+        assert!(index.search("python").is_empty());
+
+        let opts = index.resolve(Opts::builder("service")).unwrap();
+        assert_eq!(
+            opts.template_path,
+            PathBuf::from("https://github.com/username/templates.git")
+        );
+        assert_eq!(
+            opts.repository_template_path,
+            Some(PathBuf::from("service"))
+        );
+        assert_eq!(opts.git_ref.as_deref(), Some("^1"));
+
+        let opts = index
+            .resolve(Opts::builder("service").git_ref("v1.0.0"))
+            .unwrap();
+        assert_eq!(opts.git_ref.as_deref(), Some("v1.0.0"));
+
+        assert!(index.resolve(Opts::builder("unknown")).is_err());
+    }
+
+    #[test]
+    fn load_index_directory() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("service.toml"),
+            "name = \"service\"\nsource = \"https://github.com/username/service.git\"\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("cli.toml"),
+            "name = \"cli\"\nsource = \"crate:cli-template@2\"\n",
+        )
+        .unwrap();
+
+        fs::create_dir(dir.path().join("library")).unwrap();
+        fs::write(
+            dir.path().join("library").join(SCAFFOLD_FILENAME),
+            "[template]\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("library").join("Cargo.toml"), "[package]\n").unwrap();
+
+        let index = TemplateIndex::load(dir.path().to_str()).unwrap();
+        let names = index.entries().iter().map(|e| e.name()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["cli", "service"]);
+    }
+
+    #[test]
+    fn entries_without_name_are_rejected() {
+        let dir = tempdir().unwrap();
+        let index_file = dir.path().join(INDEX_FILENAME);
+        fs::write(&index_file, "[[templates]]\nsource = \"./missing\"\n").unwrap();
+        assert!(TemplateIndex::load(index_file.to_str()).is_err());
+    }
+}
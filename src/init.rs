@@ -0,0 +1,513 @@
+use anyhow::{anyhow, Context, Result};
+use clap::Parser;
+use console::{Emoji, Style};
+use dialoguer::{Confirm, Input};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use toml_edit::{value, Array, DocumentMut, Item, Table};
+use walkdir::WalkDir;
+
+use crate::{build_globset, validate::RESERVED_PARAMETERS, SCAFFOLD_FILENAME};
+
+/// Paths excluded from the template by default
+const DEFAULT_EXCLUDES: &[&str] = &["target", ".git"];
+
+/// InitOpts: the options to turn an existing project into a template.
+#[derive(Parser, Debug, Default)]
+pub struct InitOpts {
+    /// Project to turn into a template
+    #[arg(name = "project", required = true)]
+    project_path: PathBuf,
+
+    /// Template directory to create
+    #[arg(name = "template", required = true)]
+    template_path: PathBuf,
+
+    /// Literal string to replace by a parameter in <literal>=<parameter> format (and so skip the prompts)
+    #[arg(long = "replace")]
+    replacements: Vec<String>,
+
+    /// Override the template directory if it exists
+    #[arg(short = 'f', long = "force")]
+    force: bool,
+}
+
+impl InitOpts {
+    /// Builder function for the `InitOpts` structure
+    pub fn builder<P: Into<PathBuf>, T: Into<PathBuf>>(project_path: P, template_path: T) -> Self {
+        Self {
+            project_path: project_path.into(),
+            template_path: template_path.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Replace a literal string by a parameter instead of asking which ones to replace
+    pub fn replace<L: Into<String>, P: Into<String>>(mut self, literal: L, parameter: P) -> Self {
+        self.replacements
+            .push(format!("{}={}", literal.into(), parameter.into()));
+        self
+    }
+
+    /// Override the template directory if it exists
+    pub fn force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+}
+
+/// A literal string of the project replaced by a parameter
+struct Replacement {
+    literal: String,
+    parameter: String,
+}
+
+/// Copy a project into a template directory, replacing literal strings by parameters in the
+/// file contents and paths, and write its `.scaffold.toml`.
+pub fn init(opts: InitOpts) -> Result<()> {
+    if !opts.project_path.is_dir() {
+        return Err(anyhow!(
+            "cannot find project directory {:?}",
+            opts.project_path
+        ));
+    }
+    if opts.template_path.exists() {
+        if !opts.force {
+            return Err(anyhow!(
+                "cannot create {} because it already exists",
+                opts.template_path.to_string_lossy()
+            ));
+        }
+        fs::remove_dir_all(&opts.template_path).with_context(|| "Cannot remove directory")?;
+    }
+
+    let crate_name = crate_name(&opts.project_path);
+    let replacements = if opts.replacements.is_empty() {
+        ask_replacements(crate_name.as_deref())?
+    } else {
+        opts.replacements
+            .iter()
+            .map(|replacement| {
+                let (literal, parameter) = replacement
+                    .split_once('=')
+                    .filter(|(literal, parameter)| !literal.is_empty() && !parameter.is_empty())
+                    .ok_or_else(|| anyhow!("invalid argument: {}", replacement))?;
+                Ok(Replacement {
+                    literal: literal.to_string(),
+                    parameter: parameter.to_string(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?
+    };
+    let variants = variants(&replacements);
+
+    let cyan = Style::new().cyan();
+    println!(
+        "{} {}",
+        Emoji("🔄", ""),
+        cyan.apply_to("Creating template…")
+    );
+
+    let excludes = build_globset(Some(
+        &DEFAULT_EXCLUDES
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>(),
+    ))?;
+    let mut disable_templating = Vec::new();
+    let entries = WalkDir::new(&opts.project_path)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| {
+            !excludes.is_match(
+                entry
+                    .path()
+                    .strip_prefix(&opts.project_path)
+                    .unwrap_or(entry.path()),
+            )
+        });
+    for entry in entries {
+        let entry = entry.map_err(|e| anyhow!("cannot read entry : {}", e))?;
+        let entry_path = entry.path().strip_prefix(&opts.project_path)?;
+        if entry_path.as_os_str().is_empty() || entry_path == Path::new(SCAFFOLD_FILENAME) {
+            continue;
+        }
+
+        let template_entry_path = entry_path
+            .iter()
+            .map(|component| templatize(&component.to_string_lossy(), &variants))
+            .collect::<PathBuf>();
+        let target = opts.template_path.join(&template_entry_path);
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&target)
+                .map_err(|e| anyhow!("cannot create directory {target:?} : {}", e))?;
+            continue;
+        }
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| anyhow!("cannot create directory {parent:?} : {}", e))?;
+        }
+        let content = fs::read(entry.path())
+            .map_err(|e| anyhow!("cannot read file {entry_path:?} : {}", e))?;
+        let content = match String::from_utf8(content) {
+            Ok(content) => templatize(&content, &variants).into_bytes(),
+            Err(e) => {
+                // Binary files are copied as is
+                disable_templating.push(template_entry_path.to_string_lossy().to_string());
+                e.into_bytes()
+            }
+        };
+        fs::write(&target, content).map_err(|e| anyhow!("cannot write file {target:?} : {}", e))?;
+        let permissions = entry
+            .metadata()
+            .map_err(|e| anyhow!("cannot get metadata for path : {}", e))?
+            .permissions();
+        fs::set_permissions(&target, permissions)
+            .map_err(|e| anyhow!("cannot set permissions for {target:?} : {}", e))?;
+    }
+
+    let template_name = crate_name.or_else(|| {
+        opts.project_path
+            .canonicalize()
+            .ok()?
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+    });
+    let scaffold_file = opts.template_path.join(SCAFFOLD_FILENAME);
+    fs::write(
+        &scaffold_file,
+        scaffold_description(template_name.as_deref(), &replacements, &disable_templating),
+    )
+    .map_err(|e| anyhow!("cannot write file {scaffold_file:?} : {}", e))?;
+
+    println!(
+        "{} {}",
+        Emoji("✅", ""),
+        Style::new().green().apply_to(format!(
+            "Your template is ready in {}, review its {SCAFFOLD_FILENAME}",
+            opts.template_path.to_string_lossy()
+        ))
+    );
+
+    Ok(())
+}
+
+/// Name of the package in the Cargo.toml of the project
+fn crate_name(project_path: &Path) -> Option<String> {
+    let manifest = fs::read_to_string(project_path.join("Cargo.toml")).ok()?;
+    let manifest = toml::from_str::<toml::Table>(&manifest).ok()?;
+    manifest
+        .get("package")?
+        .get("name")?
+        .as_str()
+        .map(String::from)
+}
+
+fn ask_replacements(crate_name: Option<&str>) -> Result<Vec<Replacement>> {
+    let mut replacements = Vec::new();
+    if let Some(crate_name) = crate_name {
+        if Confirm::new()
+            .with_prompt(format!(
+                "Replace the crate name {crate_name:?} (and its snake, kebab and camel case variants) by the project name?"
+            ))
+            .default(true)
+            .interact()?
+        {
+            replacements.push(Replacement {
+                literal: crate_name.to_string(),
+                parameter: "name".to_string(),
+            });
+        }
+    }
+
+    loop {
+        let literal: String = Input::new()
+            .with_prompt("Another literal string to turn into a parameter (leave empty to finish)")
+            .allow_empty(true)
+            .interact()?;
+        if literal.is_empty() {
+            break;
+        }
+        let parameter: String = Input::new()
+            .with_prompt("Parameter name")
+            .default(split_words(&literal).join("_"))
+            .interact()?;
+        replacements.push(Replacement { literal, parameter });
+    }
+
+    Ok(replacements)
+}
+
+/// Case variants of the literals, longest first, with the handlebars expression rendering them
+fn variants(replacements: &[Replacement]) -> Vec<(String, String)> {
+    let mut variants: Vec<(String, String)> = Vec::new();
+    for Replacement { literal, parameter } in replacements {
+        let mut add = |variant: String, expression: String| {
+            if !variant.is_empty() && !variants.iter().any(|(v, _)| *v == variant) {
+                variants.push((variant, format!("{{{{{expression}}}}}")));
+            }
+        };
+        add(literal.clone(), parameter.clone());
+
+        // The case conversion helpers are only available with the `helpers` feature
+        if cfg!(feature = "helpers") {
+            let words = split_words(literal);
+            let capitalized = words.iter().map(|w| capitalize(w)).collect::<Vec<_>>();
+            add(words.join("_"), format!("to_snake_case {parameter}"));
+            add(words.join("-"), format!("to_kebab_case {parameter}"));
+            add(capitalized.concat(), format!("to_pascal_case {parameter}"));
+            if let Some((first, rest)) = words.split_first() {
+                add(
+                    format!(
+                        "{first}{}",
+                        rest.iter().map(|w| capitalize(w)).collect::<String>()
+                    ),
+                    format!("to_camel_case {parameter}"),
+                );
+            }
+            add(
+                words.join("_").to_uppercase(),
+                format!("to_screaming_snake_case {parameter}"),
+            );
+        }
+    }
+    variants.sort_by_key(|(variant, _)| std::cmp::Reverse(variant.len()));
+
+    variants
+}
+
+/// Lowercase words of an identifier written in any case
+fn split_words(literal: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut previous_lowercase = false;
+    for c in literal.chars() {
+        if !c.is_alphanumeric() {
+            words.push(std::mem::take(&mut word));
+            previous_lowercase = false;
+            continue;
+        }
+        if c.is_uppercase() && previous_lowercase {
+            words.push(std::mem::take(&mut word));
+        }
+        previous_lowercase = c.is_lowercase() || c.is_numeric();
+        word.extend(c.to_lowercase());
+    }
+    words.push(word);
+    words.retain(|w| !w.is_empty());
+
+    words
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Escape the existing handlebars expressions and replace the literals by their expression
+fn templatize(content: &str, variants: &[(String, String)]) -> String {
+    let content = content.replace("{{", "\\{{");
+    let mut output = String::with_capacity(content.len());
+    let mut rest = content.as_str();
+    'outer: while let Some(c) = rest.chars().next() {
+        let previous = content[..content.len() - rest.len()].chars().next_back();
+        for (variant, expression) in variants {
+            if let Some(stripped) = rest.strip_prefix(variant.as_str()) {
+                if !is_word(variant, previous, stripped.chars().next()) {
+                    continue;
+                }
+                output.push_str(expression);
+                rest = stripped;
+                continue 'outer;
+            }
+        }
+        output.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+
+    output
+}
+
+/// Whether a variant found between `previous` and `next` is a whole identifier or word, so that
+/// `app` is not replaced in `application` while `MyApp` still is in `MyAppConfig`
+fn is_word(variant: &str, previous: Option<char>, next: Option<char>) -> bool {
+    let starts_word = match (previous, variant.chars().next()) {
+        (Some(previous), Some(first)) if previous.is_alphanumeric() => {
+            (previous.is_lowercase() || previous.is_numeric()) && first.is_uppercase()
+        }
+        _ => true,
+    };
+    let ends_word = match (variant.chars().next_back(), next) {
+        (Some(last), Some(next)) if next.is_alphanumeric() => {
+            (last.is_lowercase() || last.is_numeric()) && next.is_uppercase()
+        }
+        _ => true,
+    };
+
+    starts_word && ends_word
+}
+
+/// Starter `.scaffold.toml` declaring the parameters
+fn scaffold_description(
+    template_name: Option<&str>,
+    replacements: &[Replacement],
+    disable_templating: &[String],
+) -> String {
+    let mut document = DocumentMut::new();
+    let mut template = Table::new();
+    if let Some(name) = template_name {
+        template["name"] = value(name);
+    }
+    template["exclude"] = value(DEFAULT_EXCLUDES.iter().copied().collect::<Array>());
+    if !disable_templating.is_empty() {
+        template["disable_templating"] = value(disable_templating.iter().collect::<Array>());
+    }
+    document["template"] = Item::Table(template);
+
+    let mut parameters = Table::new();
+    parameters.set_implicit(true);
+    for Replacement { literal, parameter } in replacements {
+        // Reserved parameters like the project name are provided by cargo-scaffold
+        if RESERVED_PARAMETERS.contains(&parameter.as_str()) || parameters.contains_key(parameter) {
+            continue;
+        }
+        let mut table = Table::new();
+        table["type"] = value("string");
+        table["message"] = value(format!("What is the {}?", split_words(parameter).join(" ")));
+        table["default"] = value(literal);
+        parameters[parameter] = Item::Table(table);
+    }
+    if !parameters.is_empty() {
+        document["parameters"] = Item::Table(parameters);
+    }
+
+    document.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Opts, ScaffoldDescription};
+    use indexmap::IndexMap;
+    use tempfile::tempdir;
+
+    #[test]
+    #[cfg(feature = "helpers")]
+    fn words_and_variants() {
+        assert_eq!(split_words("my-crate"), vec!["my", "crate"]);
+        assert_eq!(split_words("myCrate_name"), vec!["my", "crate", "name"]);
+
+        let variants = variants(&[Replacement {
+            literal: "my-crate".to_string(),
+            parameter: "name".to_string(),
+        }]);
+        assert_eq!(
+            templatize(
+                "use my_crate::MyCrate; // {{raw}} my-crate MY_CRATE myCrate",
+                &variants
+            ),
+            "use {{to_snake_case name}}::{{to_pascal_case name}}; // \\{{raw}} {{name}} {{to_screaming_snake_case name}} {{to_camel_case name}}"
+        );
+    }
+
+    #[test]
+    fn literals_are_replaced_on_word_boundaries() {
+        let variants = variants(&[Replacement {
+            literal: "app".to_string(),
+            parameter: "name".to_string(),
+        }]);
+        assert_eq!(
+            templatize("let application = app; mapp app_config app-cli", &variants),
+            "let application = {{name}}; mapp {{name}}_config {{name}}-cli"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "helpers")]
+    fn camel_case_boundaries() {
+        let variants = variants(&[Replacement {
+            literal: "my-app".to_string(),
+            parameter: "name".to_string(),
+        }]);
+        assert_eq!(
+            templatize("MyAppConfig newMyApp MyApplication", &variants),
+            "{{to_pascal_case name}}Config new{{to_pascal_case name}} MyApplication"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "helpers")]
+    fn init_and_scaffold_template() {
+        let project = tempdir().unwrap();
+        fs::write(
+            project.path().join("Cargo.toml"),
+            "[package]\nname = \"my-crate\"\ndescription = \"A great crate\"\n",
+        )
+        .unwrap();
+        fs::create_dir_all(project.path().join("src").join("my_crate")).unwrap();
+        fs::write(
+            project.path().join("src").join("my_crate").join("mod.rs"),
+            "pub struct MyCrate;\n// {{not a variable}}\n",
+        )
+        .unwrap();
+        fs::write(project.path().join("logo.png"), [0x89, 0x50, 0xff, 0x00]).unwrap();
+        fs::create_dir_all(project.path().join("target").join("debug")).unwrap();
+        fs::write(
+            project.path().join("target").join("debug").join("my-crate"),
+            "",
+        )
+        .unwrap();
+
+        let template = tempdir().unwrap();
+        let template_path = template.path().join("template");
+        init(
+            InitOpts::builder(project.path(), &template_path)
+                .replace("my-crate", "name")
+                .replace("A great crate", "description"),
+        )
+        .unwrap();
+
+        assert!(!template_path.join("target").exists());
+        let scaffold_desc = fs::read_to_string(template_path.join(SCAFFOLD_FILENAME)).unwrap();
+        assert!(scaffold_desc.contains("disable_templating = [\"logo.png\"]"));
+        assert!(scaffold_desc.contains("[parameters.description]"));
+        assert_eq!(crate::validate::check(&scaffold_desc), vec![]);
+
+        let target = tempdir().unwrap();
+        let parameters = IndexMap::from([(
+            "description".to_string(),
+            toml::Value::from("Another crate"),
+        )]);
+        ScaffoldDescription::new(
+            Opts::builder(&template_path)
+                .project_name("other-app")
+                .target_dir(target.path())
+                .force(true),
+        )
+        .unwrap()
+        .scaffold_with_parameters(parameters)
+        .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(target.path().join("Cargo.toml")).unwrap(),
+            "[package]\nname = \"other-app\"\ndescription = \"Another crate\"\n"
+        );
+        assert_eq!(
+            fs::read_to_string(target.path().join("src").join("other_app").join("mod.rs")).unwrap(),
+            "pub struct OtherApp;\n// {{not a variable}}\n"
+        );
+        assert_eq!(
+            fs::read(target.path().join("logo.png")).unwrap(),
+            [0x89, 0x50, 0xff, 0x00]
+        );
+    }
+}
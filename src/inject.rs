@@ -0,0 +1,158 @@
+use anyhow::{anyhow, Result};
+use regex::Regex;
+
+use crate::Injection;
+
+/// Short message of a regex error, without the pattern excerpt
+pub(crate) fn regex_error(error: &regex::Error) -> String {
+    let message = error.to_string();
+    message
+        .lines()
+        .last()
+        .unwrap_or_default()
+        .trim_start_matches("error: ")
+        .to_string()
+}
+
+fn regex(pattern: &str, key: &str) -> Result<Regex> {
+    Regex::new(pattern).map_err(|e| anyhow!("invalid regex in `{key}`: {}", regex_error(&e)))
+}
+
+/// Insert the rendered content in the file content at the anchor of the injection.
+/// Returns None when the content is already present, so an injection is applied only once.
+pub(crate) fn inject(
+    file_content: &str,
+    content: &str,
+    injection: &Injection,
+) -> Result<Option<String>> {
+    let already_present = match &injection.skip_if {
+        Some(pattern) => regex(pattern, "skip_if")?.is_match(file_content),
+        None => file_content.contains(content.trim()),
+    };
+    if already_present {
+        return Ok(None);
+    }
+
+    let mut block = content.to_string();
+    if !block.ends_with('\n') {
+        block.push('\n');
+    }
+    let offset = match (&injection.before, &injection.after, &injection.marker) {
+        (Some(pattern), _, _) => {
+            let regex = regex(pattern, "before")?;
+            find_line(file_content, |line| regex.is_match(line))
+                .map(|(start, _)| start)
+                .ok_or_else(|| anyhow!("no line matches `before` {pattern:?}"))?
+        }
+        (_, Some(pattern), _) => {
+            let regex = regex(pattern, "after")?;
+            let (_, end) = find_line(file_content, |line| regex.is_match(line))
+                .ok_or_else(|| anyhow!("no line matches `after` {pattern:?}"))?;
+            end
+        }
+        (_, _, Some(marker)) => find_line(file_content, |line| line.contains(marker.as_str()))
+            .map(|(start, _)| start)
+            .ok_or_else(|| anyhow!("no line contains the `marker` {marker:?}"))?,
+        _ => file_content.len(),
+    };
+    // The content is inserted as whole lines
+    if offset > 0 && !file_content[..offset].ends_with('\n') {
+        block.insert(0, '\n');
+    }
+
+    let mut injected = file_content.to_string();
+    injected.insert_str(offset, &block);
+    Ok(Some(injected))
+}
+
+/// Start and end offsets (after its line break) of the first line matching the predicate
+fn find_line(content: &str, predicate: impl Fn(&str) -> bool) -> Option<(usize, usize)> {
+    let mut start = 0;
+    for line in content.split_inclusive('\n') {
+        let end = start + line.len();
+        if predicate(line.trim_end_matches(['\n', '\r'])) {
+            return Some((start, end));
+        }
+        start = end;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn injection(anchor: &str, pattern: &str) -> Injection {
+        toml::from_str(&format!(
+            "file = \"src/lib.rs\"\ncontent = \"\"\n{anchor} = {pattern:?}\n"
+        ))
+        .unwrap()
+    }
+
+    const LIB: &str = "mod config;\nmod db;\n\npub fn run() {}";
+
+    #[test]
+    fn anchors() {
+        assert_eq!(
+            inject(LIB, "mod api;", &injection("before", "^mod "))
+                .unwrap()
+                .unwrap(),
+            "mod api;\nmod config;\nmod db;\n\npub fn run() {}"
+        );
+        assert_eq!(
+            inject(LIB, "mod api;", &injection("after", "^mod db;"))
+                .unwrap()
+                .unwrap(),
+            "mod config;\nmod db;\nmod api;\n\npub fn run() {}"
+        );
+        assert_eq!(
+            inject(LIB, "fn api() {}", &injection("after", "^pub fn run"))
+                .unwrap()
+                .unwrap(),
+            "mod config;\nmod db;\n\npub fn run() {}\nfn api() {}\n"
+        );
+
+        let router = "Router::new()\n    .route(\"/\", get(root))\n    // scaffold:routes\n";
+        assert_eq!(
+            inject(
+                router,
+                "    .route(\"/users\", get(users))",
+                &injection("marker", "// scaffold:routes")
+            )
+            .unwrap()
+            .unwrap(),
+            "Router::new()\n    .route(\"/\", get(root))\n    .route(\"/users\", get(users))\n    // scaffold:routes\n"
+        );
+
+        let at_the_end: Injection =
+            toml::from_str("file = \"src/lib.rs\"\ncontent = \"\"\n").unwrap();
+        assert_eq!(
+            inject(LIB, "mod api;", &at_the_end).unwrap().unwrap(),
+            "mod config;\nmod db;\n\npub fn run() {}\nmod api;\n"
+        );
+    }
+
+    #[test]
+    fn idempotency_and_errors() {
+        assert_eq!(
+            inject(LIB, "mod db;\n", &injection("after", "^mod ")).unwrap(),
+            None
+        );
+        assert_eq!(
+            inject(LIB, "pub mod db;", &injection("skip_if", r"mod db;")).unwrap(),
+            None
+        );
+        assert_eq!(
+            inject(LIB, "mod api;", &injection("after", "^mod api;"))
+                .unwrap_err()
+                .to_string(),
+            "no line matches `after` \"^mod api;\""
+        );
+        assert_eq!(
+            inject(LIB, "mod api;", &injection("before", "(mod"))
+                .unwrap_err()
+                .to_string(),
+            "invalid regex in `before`: unclosed group"
+        );
+    }
+}
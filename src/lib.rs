@@ -1,9 +1,22 @@
 #![doc = include_str!("../README.md")]
+mod conflict;
 mod git;
 mod helpers;
+mod index;
+mod init;
+mod inject;
+mod lint;
+mod patch;
+mod registry;
+mod templates;
+mod transaction;
+mod validate;
+mod workspace;
 
 use std::{
-    env,
+    borrow::Cow,
+    collections::HashSet,
+    env, fmt,
     fs::{self, File},
     io::{Read, Write},
     path::{Path, PathBuf},
@@ -13,64 +26,244 @@ use std::{
 
 use anyhow::{anyhow, Context, Result};
 use clap::Parser;
+use conflict::{ConflictSummary, Resolution};
 use console::{Emoji, Style};
 use dialoguer::{Confirm, Input, MultiSelect, Select};
-use fs::OpenOptions;
-use globset::{Glob, GlobSetBuilder};
+use globset::{Glob, GlobMatcher, GlobSet, GlobSetBuilder};
 use handlebars::Handlebars;
 use helpers::ForRangHelper;
 use indexmap::IndexMap;
+use lint::Linter;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use transaction::{Mode, Transaction};
 use walkdir::WalkDir;
+use workspace::{Membership, Workspace};
 
+pub use conflict::ConflictPolicy;
+pub use index::{IndexEntry, TemplateIndex, INDEX_FILENAME};
+pub use init::{init, InitOpts};
+pub use lint::LintIssue;
+pub use templates::TEMPLATES_FILENAME;
 pub use toml::Value;
+pub use validate::Diagnostic;
 pub const SCAFFOLD_FILENAME: &str = ".scaffold.toml";
-
-#[derive(Serialize, Deserialize)]
+/// Directory of the partials of a template, unless set in its description
+const PARTIALS_DIR: &str = "partials";
+/// Directory of the script helpers of a template, unless set in its description
+const HELPERS_DIR: &str = "helpers";
+
+/// Content of the `.scaffold.toml` file of a template
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[schemars(title = "cargo-scaffold template", deny_unknown_fields)]
 pub struct ScaffoldDescription {
     template: TemplateDescription,
+    /// Parameters asked to the user, available as variables in the templates
     #[serde(default)]
     parameters: IndexMap<String, Parameter>,
     hooks: Option<Hooks>,
+    /// Contents inserted in existing or generated files, once the files are generated
+    #[serde(default)]
+    inject: Vec<Injection>,
+    /// Structured edits of TOML and JSON files, applied after the injections
+    #[serde(default)]
+    patch: Vec<Patch>,
     #[serde(skip)]
     target_dir: Option<PathBuf>,
     #[serde(skip)]
     template_path: PathBuf,
+    /// Directories of the inherited templates with their description, from the closest parent
+    #[serde(skip)]
+    parents: Vec<(PathBuf, TemplateDescription)>,
     #[serde(skip)]
     force: bool,
     #[serde(skip)]
     append: bool,
     #[serde(skip)]
+    overwrite: bool,
+    #[serde(skip)]
+    on_conflict: Option<ConflictPolicy>,
+    #[serde(skip)]
+    strict: bool,
+    #[serde(skip)]
+    strict_hooks: bool,
+    #[serde(skip)]
+    warn_binary: bool,
+    #[serde(skip)]
+    workspace: bool,
+    #[serde(skip)]
+    umask: Option<u32>,
+    #[serde(skip)]
     project_name: Option<String>,
     #[serde(skip)]
     default_parameters: IndexMap<String, Value>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub struct TemplateDescription {
+    name: Option<String>,
+    author: Option<String>,
+    version: Option<String>,
+    description: Option<String>,
+    homepage: Option<String>,
+    /// Oldest version of cargo-scaffold able to generate the template
+    min_scaffold_version: Option<String>,
+    /// Parent template (a path relative to this template, a git repository or a crate) whose
+    /// parameters, files, hooks and excludes are inherited
+    extends: Option<Extends>,
+    /// Directory whose files are registered as partials (i.e.: `{{> license_header}}`) and not
+    /// copied in the generated project, `partials` by default
+    partials: Option<String>,
+    /// Directory whose Rhai scripts are registered as helpers named after their file
+    /// (i.e.: `{{slug name}}` for `helpers/slug.rhai`) and not copied in the generated project,
+    /// `helpers` by default
+    helpers: Option<String>,
+    /// Globs of the paths which are not copied in the generated project
     exclude: Option<Vec<String>>,
+    /// Globs of the paths which are copied without templating
     disable_templating: Option<Vec<String>>,
+    /// Globs of the paths of an existing project directory which are never touched
+    protected: Option<Vec<String>>,
+    /// Notes displayed at the end of the generation
     notes: Option<String>,
+    /// Custom delimiters for files whose native syntax uses braces
+    delimiters: Option<Vec<Delimiters>>,
+    /// Explicit modes of the generated files
+    modes: Option<Vec<FileMode>>,
+}
+
+/// Unix mode (i.e.: `0o755`) of the generated files matching the glob
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct FileMode {
+    glob: String,
+    mode: u32,
+}
+
+/// Handlebars expressions delimiters used instead of `{{` and `}}` in the files matching the glob,
+/// where braces are kept as is
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct Delimiters {
+    glob: String,
+    open: String,
+    close: String,
+}
+
+/// Template extended by another one
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(untagged)]
+pub enum Extends {
+    /// Path relative to the template, git repository (ending with `.git`) or crate
+    /// (`crate:name@version`)
+    Source(String),
+    /// Template of a git repository at a given reference
+    Git(GitTemplate),
+}
+
+impl fmt::Display for Extends {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Source(source) => write!(f, "{source}"),
+            Self::Git(GitTemplate { git, git_ref, path }) => {
+                write!(f, "{git}")?;
+                if let Some(git_ref) = git_ref {
+                    write!(f, " at {git_ref}")?;
+                }
+                if let Some(path) = path {
+                    write!(f, " in {path}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Template of a git repository, like the ones given with `--git_ref` and `--path`
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct GitTemplate {
+    /// URL of the repository
+    git: String,
+    /// Branch, tag, commit or semver requirement on the tags, the default branch otherwise
+    #[serde(rename = "ref")]
+    git_ref: Option<String>,
+    /// Directory of the template in the repository
+    path: Option<String>,
+}
+
+/// Content inserted in a file of the project, before or after the first line matching a regex,
+/// before the line containing a marker comment, or at the end of the file
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct Injection {
+    /// Path of the file relative to the project directory, templated
+    file: String,
+    /// Lines to insert, templated
+    content: String,
+    /// Regex of the line before which the content is inserted
+    before: Option<String>,
+    /// Regex of the line after which the content is inserted
+    after: Option<String>,
+    /// Marker comment (i.e.: `// scaffold:routes`), the content is inserted before its line
+    marker: Option<String>,
+    /// Regex skipping the injection when the file matches it,
+    /// by default the injection is skipped when the file already contains the content
+    skip_if: Option<String>,
+}
+
+/// Edit of a TOML or JSON file of the project, keeping its formatting
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct Patch {
+    /// Path of the file relative to the project directory, templated
+    file: String,
+    /// `set` the value of the key, `append` the value (or each value of an array) to the array
+    /// unless already present, or `merge` the table into the table of the key
+    op: PatchOperation,
+    /// Dotted key of the edited value (i.e.: `workspace.members`), templated
+    key: String,
+    /// Value whose strings and keys are templated
+    #[schemars(with = "serde_json::Value")]
+    value: Value,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PatchOperation {
+    Set,
+    Append,
+    Merge,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub struct Parameter {
+    /// Prompt displayed to the user
     message: String,
     #[serde(default)]
     required: bool,
     r#type: ParameterType,
+    #[schemars(with = "Option<serde_json::Value>")]
     default: Option<Value>,
+    /// Values to choose from, for select and multiselect parameters
+    #[schemars(with = "Option<Vec<serde_json::Value>>")]
     values: Option<Vec<Value>>,
     tags: Option<Vec<String>>,
 }
 
-#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+/// Commands executed from within the generated project
+#[derive(Debug, Default, Serialize, Deserialize, Clone, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub struct Hooks {
+    /// Commands executed before scaffolding
     pre: Option<Vec<String>>,
+    /// Commands executed after scaffolding
     post: Option<Vec<String>>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum ParameterType {
     String,
@@ -121,7 +314,8 @@ pub enum ParameterType {
 #[derive(Parser, Debug, Default)]
 #[command(author, version, about, long_about=None)]
 pub struct Opts {
-    /// Specifiy your template location
+    /// Specifiy your template location (a local directory, a git repository ending with ".git"
+    /// or a crate published on a cargo registry as "crate:<name>[@<version requirement>]")
     #[arg(name = "template", required = true)]
     template_path: PathBuf,
 
@@ -129,11 +323,26 @@ pub struct Opts {
     #[arg(name = "repository_template_path", short = 'r', long = "path")]
     repository_template_path: Option<PathBuf>,
 
+    /// Name of the template to use when the location contains several templates, listed in its
+    /// scaffold-templates.toml or discovered from their .scaffold.toml (asked if not set)
+    #[arg(
+        name = "template_name",
+        long = "template",
+        conflicts_with = "repository_template_path"
+    )]
+    template_name: Option<String>,
+
     /// Full commit hash, tag or branch from which the template is cloned
-    /// (i.e.: "deed14dcbf17ba87f6659ea05755cf94cb1464ab" or "v0.5.0" or "main")
+    /// (i.e.: "deed14dcbf17ba87f6659ea05755cf94cb1464ab" or "v0.5.0" or "main"),
+    /// or a semver requirement selecting the highest matching tag (i.e.: "^1.2", "~2" or "latest")
     #[arg(name = "git_ref", short = 't', long = "git_ref")]
     git_ref: Option<String>,
 
+    /// Cargo registry index used to resolve "crate:" templates, either a sparse index URL
+    /// (i.e.: "sparse+https://my.registry/index/") or a local registry directory. Defaults to crates.io
+    #[arg(name = "registry", long = "registry")]
+    registry: Option<String>,
+
     /// Specify the name of your generated project (and so skip the prompt asking for it)
     #[arg(name = "name", short = 'n', long = "name")]
     project_name: Option<String>,
@@ -146,10 +355,22 @@ pub struct Opts {
     #[arg(short = 'f', long = "force")]
     force: bool,
 
-    /// Append files in the target directory, create directory with the project name if it doesn't already exist but doesn't overwrite existing file (use force for that kind of usage)
+    /// Append files in the target directory, create directory with the project name if it doesn't already exist, asking what to do with the existing files (see --on_conflict)
     #[arg(short = 'a', long = "append")]
     append: bool,
 
+    /// Replace the files generated by the template in the target directory if it exists, keeping the other files
+    #[arg(long = "overwrite", conflicts_with = "force")]
+    overwrite: bool,
+
+    /// What to do with the generated files which already exist when appending, asked for each file if not set
+    #[arg(long = "on_conflict", alias = "on-conflict", value_enum)]
+    on_conflict: Option<ConflictPolicy>,
+
+    /// Fail when a template references a variable which is not defined instead of rendering it as empty
+    #[arg(long = "strict")]
+    strict: bool,
+
     /// Ignored, kept for backwards compatibility [DEPRECATED]
     #[arg(short = 'p', long = "passphrase")]
     passphrase_needed: bool,
@@ -158,6 +379,30 @@ pub struct Opts {
     #[arg(short = 'k', long = "private_key_path")]
     private_key_path: Option<PathBuf>,
 
+    /// Recursively checkout the git submodules of the template repository
+    #[arg(long = "submodules")]
+    submodules: bool,
+
+    /// Only warn about unknown keys in the .scaffold.toml instead of failing, to use templates written for newer versions
+    #[arg(long = "allow_unknown_keys")]
+    allow_unknown_keys: bool,
+
+    /// Fail when a hook exits with an error, rolling the generation back
+    #[arg(long = "strict_hooks")]
+    strict_hooks: bool,
+
+    /// Display a warning for each binary file copied without templating
+    #[arg(long = "warn_binary")]
+    warn_binary: bool,
+
+    /// Umask applied to the modes of the template files (i.e.: "022"), ignored on Windows
+    #[arg(long = "umask", value_parser = parse_octal)]
+    umask: Option<u32>,
+
+    /// Register the generated crate in the members of the enclosing cargo workspace
+    #[arg(long = "workspace")]
+    workspace: bool,
+
     /// Supply parameters via the command line in <name>=<value> format
     #[arg(long = "param")]
     parameters: Vec<String>,
@@ -181,12 +426,24 @@ impl Opts {
         self
     }
 
+    /// Set the name of the template to use when the location contains several templates
+    pub fn template_name<T: Into<String>>(mut self, name: T) -> Self {
+        self.template_name = Some(name.into());
+        self
+    }
+
     /// Set the git reference
     pub fn git_ref<T: Into<String>>(mut self, gitref: T) -> Self {
         let _ = self.git_ref.replace(gitref.into());
         self
     }
 
+    /// Set the cargo registry index used for "crate:" templates
+    pub fn registry<T: Into<String>>(mut self, registry: T) -> Self {
+        let _ = self.registry.replace(registry.into());
+        self
+    }
+
     /// Set the project name
     pub fn project_name<T: Into<String>>(mut self, name: T) -> Self {
         let _ = self.project_name.replace(name.into());
@@ -211,6 +468,24 @@ impl Opts {
         self
     }
 
+    /// Replace the generated files in the target directory if exists, keeping the other files
+    pub fn overwrite(mut self, overwrite: bool) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    /// What to do with the existing files when appending, instead of asking for each of them
+    pub fn on_conflict(mut self, policy: ConflictPolicy) -> Self {
+        self.on_conflict = Some(policy);
+        self
+    }
+
+    /// Fail when a template references a variable which is not defined
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
     /// Is Passphrase needed (prompt user)
     pub fn passphrase_needed(mut self, needed: bool) -> Self {
         self.passphrase_needed = needed;
@@ -223,6 +498,42 @@ impl Opts {
         self
     }
 
+    /// Recursively checkout the git submodules of the template repository
+    pub fn submodules(mut self, submodules: bool) -> Self {
+        self.submodules = submodules;
+        self
+    }
+
+    /// Only warn about unknown keys in the .scaffold.toml instead of failing
+    pub fn allow_unknown_keys(mut self, allow: bool) -> Self {
+        self.allow_unknown_keys = allow;
+        self
+    }
+
+    /// Fail when a hook exits with an error, rolling the generation back
+    pub fn strict_hooks(mut self, strict_hooks: bool) -> Self {
+        self.strict_hooks = strict_hooks;
+        self
+    }
+
+    /// Display a warning for each binary file copied without templating
+    pub fn warn_binary(mut self, warn: bool) -> Self {
+        self.warn_binary = warn;
+        self
+    }
+
+    /// Register the generated crate in the members of the enclosing cargo workspace
+    pub fn workspace(mut self, workspace: bool) -> Self {
+        self.workspace = workspace;
+        self
+    }
+
+    /// Set the umask applied to the modes of the template files
+    pub fn umask(mut self, umask: u32) -> Self {
+        let _ = self.umask.replace(umask);
+        self
+    }
+
     /// Set the parameters (supplied as `vec!["key1=value1", "key2=value2"]`).
     pub fn parameters<T: Into<String>>(mut self, params: Vec<T>) -> Self {
         let _ = std::mem::replace(
@@ -239,7 +550,7 @@ impl Opts {
 impl ScaffoldDescription {
     pub fn new(opts: Opts) -> Result<Self> {
         let mut default_parameters = IndexMap::new();
-        for param in opts.parameters {
+        for param in &opts.parameters {
             let split = param.splitn(2, '=').collect::<Vec<_>>();
             if split.len() != 2 {
                 return Err(anyhow!("invalid argument: {}", param));
@@ -250,48 +561,335 @@ impl ScaffoldDescription {
             default_parameters.insert("name".to_string(), Value::String(name.to_string()));
         }
 
-        let mut template_path = opts.template_path.to_string_lossy().to_string();
-        let mut scaffold_desc: ScaffoldDescription = {
-            if template_path.ends_with(".git") {
-                let tmp_dir = env::temp_dir().join(format!("{:x}", md5::compute(&template_path)));
-                if tmp_dir.exists() {
-                    fs::remove_dir_all(&tmp_dir)?;
-                }
-                fs::create_dir_all(&tmp_dir)?;
-                git::clone(
-                    &template_path,
-                    opts.git_ref.as_deref(),
-                    &tmp_dir,
-                    opts.private_key_path.as_deref(),
-                )?;
-                template_path = match opts.repository_template_path {
-                    Some(sub_path) => tmp_dir.join(sub_path).to_string_lossy().to_string(),
-                    None => tmp_dir.to_string_lossy().to_string(),
-                };
-            }
-            let mut scaffold_file =
-                File::open(PathBuf::from(&template_path).join(SCAFFOLD_FILENAME))
-                    .with_context(|| format!("cannot open .scaffold.toml in {}", template_path))?;
-            let mut scaffold_desc_str = String::new();
-            scaffold_file.read_to_string(&mut scaffold_desc_str)?;
-            toml::from_str(&scaffold_desc_str)?
-        };
+        let template_path = Self::fetch_template(&opts)?;
+        let mut scaffold_desc = Self::read_description(&template_path, opts.allow_unknown_keys)?;
+        scaffold_desc.template_path = template_path;
+        scaffold_desc.extend_parents(&opts)?;
 
         scaffold_desc.target_dir = opts.target_dir;
         scaffold_desc.force = opts.force;
-        scaffold_desc.template_path = PathBuf::from(template_path);
         scaffold_desc.project_name = opts.project_name;
         scaffold_desc.append = opts.append;
+        scaffold_desc.overwrite = opts.overwrite;
+        scaffold_desc.on_conflict = opts.on_conflict;
+        scaffold_desc.strict = opts.strict;
+        scaffold_desc.strict_hooks = opts.strict_hooks;
+        scaffold_desc.warn_binary = opts.warn_binary;
+        scaffold_desc.umask = opts.umask;
+        scaffold_desc.workspace = opts.workspace;
         scaffold_desc.default_parameters = default_parameters;
+        scaffold_desc.check_scaffold_version()?;
 
         Ok(scaffold_desc)
     }
 
+    /// JSON Schema of the .scaffold.toml file, for editors to validate and autocomplete it
+    pub fn json_schema() -> String {
+        let schema = schemars::schema_for!(ScaffoldDescription);
+        serde_json::to_string_pretty(&schema).expect("a schema is always serializable") + "\n"
+    }
+
+    /// Check the template without scaffolding it, returning all the problems found in its .scaffold.toml
+    pub fn validate(opts: Opts) -> Result<Vec<Diagnostic>> {
+        let template_path = Self::fetch_template(&opts)?;
+        let content = fs::read_to_string(template_path.join(SCAFFOLD_FILENAME))
+            .with_context(|| format!("cannot open .scaffold.toml in {template_path:?}"))?;
+
+        Ok(validate::check(&content))
+    }
+
+    /// Fetch the template if it's located in a git repository or on a registry and returns its local path
+    fn fetch_template(opts: &Opts) -> Result<PathBuf> {
+        let template_path = opts.template_path.to_string_lossy().to_string();
+        Self::fetch_template_in(opts, &template_path)
+    }
+
+    /// Fetch the template in a temporary directory named after the key
+    fn fetch_template_in(opts: &Opts, key: &str) -> Result<PathBuf> {
+        let template_path = opts.template_path.to_string_lossy().to_string();
+        let fetched_path = if let Some(spec) = template_path.strip_prefix(registry::CRATE_PREFIX) {
+            let tmp_dir = Self::fetch_dir(key)?;
+            registry::fetch(spec, opts.registry.as_deref(), &tmp_dir)?
+        } else if template_path.ends_with(".git") {
+            let tmp_dir = Self::fetch_dir(key)?;
+            git::clone(
+                &template_path,
+                opts.git_ref.as_deref(),
+                &tmp_dir,
+                opts.private_key_path.as_deref(),
+                opts.submodules,
+                opts.repository_template_path.as_deref(),
+            )?;
+            tmp_dir
+        } else {
+            return templates::select(&opts.template_path, opts.template_name.as_deref());
+        };
+
+        match &opts.repository_template_path {
+            Some(sub_path) => Ok(fetched_path.join(sub_path)),
+            None => templates::select(&fetched_path, opts.template_name.as_deref()),
+        }
+    }
+
+    /// Empty temporary directory in which the template named after the key is fetched
+    fn fetch_dir(key: &str) -> Result<PathBuf> {
+        let tmp_dir = Self::fetch_path(key);
+        if tmp_dir.exists() {
+            fs::remove_dir_all(&tmp_dir)?;
+        }
+        fs::create_dir_all(&tmp_dir)?;
+
+        Ok(tmp_dir)
+    }
+
+    fn fetch_path(key: &str) -> PathBuf {
+        env::temp_dir().join(format!("{:x}", md5::compute(key)))
+    }
+
+    /// Layer the template on the templates it extends: their parameters come first and are
+    /// overridden by the ones of the same name, hooks are concatenated and excludes combined
+    fn extend_parents(&mut self, opts: &Opts) -> Result<()> {
+        let mut child_path = self.template_path.clone();
+        // Clone of the git repository the child template comes from
+        let template_path = opts.template_path.to_string_lossy();
+        let mut repository_dir = template_path
+            .ends_with(".git")
+            .then(|| Self::fetch_path(&template_path));
+        let mut extends = self.template.extends.clone();
+        let mut visited = vec![fs::canonicalize(&child_path)?];
+        while let Some(parent) = extends {
+            let source = parent.to_string();
+            // Like top-level templates, parents are local paths, git repositories or crates
+            let mut parent_opts = match &parent {
+                Extends::Source(source)
+                    if !source.starts_with(registry::CRATE_PREFIX) && !source.ends_with(".git") =>
+                {
+                    // Only the directory of the child was checked out from its repository,
+                    // the parent is checked out from the same commit
+                    if let Some(repository_dir) = &repository_dir {
+                        let subpath = child_path.strip_prefix(repository_dir)?.join(source);
+                        git::checkout_template(
+                            repository_dir,
+                            &subpath,
+                            opts.private_key_path.as_deref(),
+                            opts.submodules,
+                        )
+                        .with_context(|| format!("cannot fetch the parent template {source:?}"))?;
+                    }
+                    Opts::builder(child_path.join(source))
+                }
+                Extends::Source(source) => {
+                    repository_dir = None;
+                    Opts::builder(source)
+                }
+                Extends::Git(GitTemplate { git, git_ref, path }) => {
+                    repository_dir = None;
+                    let mut parent_opts = Opts::builder(git);
+                    if let Some(git_ref) = git_ref {
+                        parent_opts = parent_opts.git_ref(git_ref);
+                    }
+                    if let Some(path) = path {
+                        parent_opts = parent_opts.repository_template_path(path);
+                    }
+                    parent_opts
+                }
+            }
+            .submodules(opts.submodules);
+            if let Some(registry) = &opts.registry {
+                parent_opts = parent_opts.registry(registry);
+            }
+            if let Some(private_key_path) = &opts.private_key_path {
+                parent_opts = parent_opts.private_key_path(private_key_path);
+            }
+            // Not in the directory of a repository the child may come from
+            let key = format!("{source} extended by {child_path:?}");
+            let parent_path = Self::fetch_template_in(&parent_opts, &key)
+                .with_context(|| format!("cannot fetch the parent template {source:?}"))?;
+            if parent_opts
+                .template_path
+                .to_string_lossy()
+                .ends_with(".git")
+            {
+                repository_dir = Some(Self::fetch_path(&key));
+            }
+            let canonical_path = fs::canonicalize(&parent_path)
+                .with_context(|| format!("cannot find the parent template {source:?}"))?;
+            if visited.contains(&canonical_path) {
+                return Err(anyhow!(
+                    "the template {parent_path:?} extends itself through {source:?}"
+                ));
+            }
+            visited.push(canonical_path);
+
+            let parent = Self::read_description(&parent_path, opts.allow_unknown_keys)?;
+            parent.check_scaffold_version()?;
+            extends = parent.template.extends.clone();
+            let template = parent.template.clone();
+            self.inherit(parent);
+            self.parents.push((parent_path.clone(), template));
+            child_path = parent_path;
+        }
+
+        Ok(())
+    }
+
+    /// Merge the description of a parent template into this one, which takes precedence
+    fn inherit(&mut self, parent: Self) {
+        let mut parameters = parent.parameters;
+        parameters.extend(std::mem::take(&mut self.parameters));
+        self.parameters = parameters;
+
+        self.hooks = match (parent.hooks, self.hooks.take()) {
+            (Some(parent), Some(child)) => Some(Hooks {
+                pre: concat(parent.pre, child.pre),
+                post: concat(parent.post, child.post),
+            }),
+            (parent, child) => parent.or(child),
+        };
+        self.inject.splice(0..0, parent.inject);
+        self.patch.splice(0..0, parent.patch);
+
+        let template = &mut self.template;
+        let parent = parent.template;
+        template.exclude = concat(parent.exclude, template.exclude.take());
+        template.disable_templating = concat(
+            parent.disable_templating,
+            template.disable_templating.take(),
+        );
+        template.protected = concat(parent.protected, template.protected.take());
+        // The first delimiters or mode matching a file apply, the ones of the child come first
+        template.delimiters = concat(template.delimiters.take(), parent.delimiters);
+        template.modes = concat(template.modes.take(), parent.modes);
+        template.notes = template.notes.take().or(parent.notes);
+    }
+
+    /// Directories of the template and of its parents with their own description, the files
+    /// of each template override the ones of the templates after it
+    fn layers(&self) -> impl Iterator<Item = (&Path, &TemplateDescription)> {
+        std::iter::once((self.template_path.as_path(), &self.template)).chain(
+            self.parents
+                .iter()
+                .map(|(template_path, template)| (template_path.as_path(), template)),
+        )
+    }
+
+    /// Read and check the .scaffold.toml of the template
+    fn read_description(template_path: &Path, allow_unknown_keys: bool) -> Result<Self> {
+        let mut scaffold_file = File::open(template_path.join(SCAFFOLD_FILENAME))
+            .with_context(|| format!("cannot open .scaffold.toml in {template_path:?}"))?;
+        let mut scaffold_desc_str = String::new();
+        scaffold_file.read_to_string(&mut scaffold_desc_str)?;
+
+        let (unknown_keys, errors): (Vec<_>, Vec<_>) = validate::check(&scaffold_desc_str)
+            .into_iter()
+            .partition(|d| d.unknown_key);
+        let errors = if allow_unknown_keys {
+            let yellow = Style::new().yellow();
+            for diagnostic in unknown_keys {
+                println!(
+                    "{} {}",
+                    Emoji("⚠️ ", ""),
+                    yellow.apply_to(format!("{SCAFFOLD_FILENAME}:{diagnostic}"))
+                );
+            }
+            errors
+        } else {
+            unknown_keys.into_iter().chain(errors).collect()
+        };
+        if !errors.is_empty() {
+            let errors = errors
+                .iter()
+                .map(|d| format!("  {SCAFFOLD_FILENAME}:{d}"))
+                .collect::<Vec<_>>()
+                .join("\n");
+            return Err(anyhow!(
+                "invalid .scaffold.toml in {template_path:?} (use --allow_unknown_keys to ignore unknown keys):\n{errors}"
+            ));
+        }
+
+        Ok(toml::from_str(&scaffold_desc_str)?)
+    }
+
     pub fn name(&self) -> Option<String> {
         self.project_name.clone()
     }
 
-    fn create_dir(&self, name: &str) -> Result<PathBuf> {
+    /// Name of the template
+    pub fn template_name(&self) -> Option<&str> {
+        self.template.name.as_deref()
+    }
+
+    /// Author of the template
+    pub fn template_author(&self) -> Option<&str> {
+        self.template.author.as_deref()
+    }
+
+    /// Version of the template
+    pub fn template_version(&self) -> Option<&str> {
+        self.template.version.as_deref()
+    }
+
+    /// Description of the template
+    pub fn template_description(&self) -> Option<&str> {
+        self.template.description.as_deref()
+    }
+
+    /// Homepage of the template
+    pub fn template_homepage(&self) -> Option<&str> {
+        self.template.homepage.as_deref()
+    }
+
+    /// Oldest version of cargo-scaffold able to generate the template
+    pub fn min_scaffold_version(&self) -> Option<&str> {
+        self.template.min_scaffold_version.as_deref()
+    }
+
+    fn check_scaffold_version(&self) -> Result<()> {
+        let Some(min_version) = self.min_scaffold_version() else {
+            return Ok(());
+        };
+        let requirement = semver::VersionReq::parse(&format!(">={min_version}"))
+            .with_context(|| format!("invalid min_scaffold_version {min_version:?}"))?;
+        let current_version = semver::Version::parse(env!("CARGO_PKG_VERSION"))?;
+        if !requirement.matches(&current_version) {
+            return Err(anyhow!(
+                "this template requires cargo-scaffold {min_version} or newer but {current_version} is installed, upgrade it with `cargo install cargo-scaffold`"
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn print_header(&self) {
+        let Some(template_name) = self.template_name() else {
+            return;
+        };
+        let cyan = Style::new().cyan();
+        let dim = Style::new().dim();
+        let version = self
+            .template_version()
+            .map(|v| format!(" v{v}"))
+            .unwrap_or_default();
+        let author = self
+            .template_author()
+            .map(|a| format!(" by {a}"))
+            .unwrap_or_default();
+        println!(
+            "{} {}{}",
+            Emoji("📦", ""),
+            cyan.apply_to(format!("{template_name}{version}")),
+            dim.apply_to(author)
+        );
+        if let Some(description) = self.template_description() {
+            println!("   {}", description.trim());
+        }
+        if let Some(homepage) = self.template_homepage() {
+            println!("   {}", dim.apply_to(homepage));
+        }
+    }
+
+    /// Absolute path of the project directory, and how the generated files are moved into it
+    fn project_dir(&self, name: &str) -> Result<(PathBuf, Mode)> {
         let mut dir_path = self
             .target_dir
             .clone()
@@ -301,20 +899,36 @@ impl ScaffoldDescription {
         if self.target_dir.is_none() {
             dir_path = dir_path.join(name);
         }
-        if dir_path.exists() {
-            if !self.force && !self.append {
+        let mode = if dir_path.exists() {
+            if !self.force && !self.append && !self.overwrite {
                 return Err(anyhow!(
                     "cannot create {} because it already exists",
                     dir_path.to_string_lossy()
                 ));
+            } else if self.overwrite {
+                println!(
+                    "{} {}",
+                    Emoji("🔄", ""),
+                    cyan.apply_to(format!(
+                        "Overwrite files in directory {}…",
+                        dir_path.to_string_lossy()
+                    )),
+                );
+                Mode::Overwrite
             } else if self.force {
+                if git::has_uncommitted_changes(&dir_path)? && !confirm_dirty_replace(&dir_path)? {
+                    return Err(anyhow!(
+                        "{} has uncommitted changes in its git repository, commit them or use --overwrite",
+                        dir_path.to_string_lossy()
+                    ));
+                }
                 println!(
                     "{} {}",
                     Emoji("🔄", ""),
                     cyan.apply_to("Override directory…"),
                 );
-                fs::remove_dir_all(&dir_path).with_context(|| "Cannot remove directory")?;
-            } else if self.append {
+                Mode::Replace
+            } else {
                 println!(
                     "{} {}",
                     Emoji("🔄", ""),
@@ -323,6 +937,7 @@ impl ScaffoldDescription {
                         dir_path.to_string_lossy()
                     )),
                 );
+                Mode::Merge
             }
         } else {
             println!(
@@ -333,11 +948,25 @@ impl ScaffoldDescription {
                     dir_path.to_string_lossy()
                 )),
             );
-        }
-        fs::create_dir_all(&dir_path).with_context(|| "Cannot create directory")?;
-        let path = fs::canonicalize(dir_path).with_context(|| "Cannot canonicalize path")?;
+            Mode::Create
+        };
+
+        let path = match (mode, dir_path.parent(), dir_path.file_name()) {
+            (Mode::Create, Some(parent), Some(file_name)) => {
+                let parent = if parent.as_os_str().is_empty() {
+                    Path::new(".")
+                } else {
+                    parent
+                };
+                fs::create_dir_all(parent).with_context(|| "Cannot create directory")?;
+                fs::canonicalize(parent)
+                    .with_context(|| "Cannot canonicalize path")?
+                    .join(file_name)
+            }
+            _ => fs::canonicalize(&dir_path).with_context(|| "Cannot canonicalize path")?,
+        };
 
-        Ok(path)
+        Ok((path, mode))
     }
 
     /// Launch prompt to the user to ask for different parameters
@@ -369,6 +998,7 @@ impl ScaffoldDescription {
 
     /// Scaffold the project with the template
     pub fn scaffold(&self) -> Result<()> {
+        self.print_header();
         let mut parameters = self.default_parameters.clone();
         parameters.append(&mut self.fetch_parameters_value()?);
         self.internal_scaffold(parameters)
@@ -377,6 +1007,7 @@ impl ScaffoldDescription {
     /// Scaffold the project with the given parameters defined in the .scaffold.toml without prompting any inputs
     /// It's a non-interactive mode
     pub fn scaffold_with_parameters(&self, mut parameters: IndexMap<String, Value>) -> Result<()> {
+        self.print_header();
         let mut default_parameters = self.default_parameters.clone();
         if let Some(name) = &self.project_name {
             parameters.insert("name".to_string(), Value::String(name.clone()));
@@ -389,28 +1020,10 @@ impl ScaffoldDescription {
     }
 
     fn internal_scaffold(&self, mut parameters: IndexMap<String, Value>) -> Result<()> {
-        let excludes = match &self.template.exclude {
-            Some(exclude) => {
-                let mut builder = GlobSetBuilder::new();
-                for ex in exclude {
-                    builder.add(Glob::new(ex.trim_start_matches("./"))?);
-                }
-
-                builder.build()?
-            }
-            None => GlobSetBuilder::new().build()?,
-        };
-        let disable_templating = match &self.template.disable_templating {
-            Some(exclude) => {
-                let mut builder = GlobSetBuilder::new();
-                for ex in exclude {
-                    builder.add(Glob::new(ex.trim_start_matches("./"))?);
-                }
-
-                builder.build()?
-            }
-            None => GlobSetBuilder::new().build()?,
-        };
+        let excludes = build_globset(self.template.exclude.as_deref())?;
+        let disable_templating = build_globset(self.template.disable_templating.as_deref())?;
+        let delimiters = self.delimiters()?;
+        let modes = self.modes()?;
 
         let name = parameters
             .get("name")
@@ -418,17 +1031,26 @@ impl ScaffoldDescription {
             .as_str()
             .expect("project name must be a string")
             .to_string();
-        let dir_path = self.create_dir(&name)?;
+        let (project_path, mode) = self.project_dir(&name)?;
         parameters.insert(
             "target_dir".to_string(),
-            Value::String(dir_path.to_str().unwrap_or_default().to_string()),
+            Value::String(project_path.to_str().unwrap_or_default().to_string()),
         );
+        let workspace = Workspace::find(&project_path)?;
+        if let Some(workspace) = &workspace {
+            parameters.extend(workspace.parameters());
+        }
+        // The project is generated in a staging directory, moved into place once complete
+        let mut transaction = Transaction::begin(&project_path, mode)?;
+        let protected = match mode {
+            Mode::Create => Vec::new(),
+            _ => self.protected_paths(&project_path)?,
+        };
+        transaction.protect(protected.clone());
+        let mut conflicts = ConflictSummary::default();
+        let dir_path = transaction.staging_path().to_path_buf();
 
-        let mut template_engine = Handlebars::new();
-        template_engine.set_strict_mode(false);
-        #[cfg(feature = "helpers")]
-        handlebars_misc_helpers::setup_handlebars(&mut template_engine);
-        template_engine.register_helper("forRange", Box::new(ForRangHelper));
+        let template_engine = self.template_engine()?;
 
         // pre-hooks
         if let Some(Hooks {
@@ -444,47 +1066,36 @@ impl ScaffoldDescription {
                     cyan.apply_to("Triggering pre-hooks…"),
                 );
             }
-            let commands = commands
-                .iter()
-                .map(|c| template_engine.render_template(c, &parameters).ok())
-                .map(|v| v.unwrap())
-                .collect::<Vec<String>>();
-
-            self.run_hooks(&dir_path, &commands)?;
+            // They see the existing project directory when its files are kept, otherwise they
+            // run in the staging directory which becomes the project directory. The staging
+            // directory is removed on failure. `target_dir` is the directory they run in
+            let hooks_path = match mode {
+                Mode::Merge | Mode::Overwrite => &project_path,
+                Mode::Create | Mode::Replace => &dir_path,
+            };
+            let mut hook_parameters = parameters.clone();
+            hook_parameters.insert(
+                "target_dir".to_string(),
+                Value::String(hooks_path.to_str().unwrap_or_default().to_string()),
+            );
+            let commands = render_hooks(&template_engine, commands, &hook_parameters)?;
+            self.run_hooks(hooks_path, &commands)?;
         }
 
-        // List entries inside directory
-        let entries = WalkDir::new(&self.template_path)
-            .into_iter()
-            .filter_entry(|entry| {
-                // Do not include git files
-                if entry
-                    .path()
-                    .components()
-                    .any(|c| c == std::path::Component::Normal(".git".as_ref()))
-                {
-                    return false;
-                }
-
-                if entry.depth() == 1 && entry.file_name() == SCAFFOLD_FILENAME {
-                    return false;
-                }
-
-                !excludes.is_match(
-                    entry
-                        .path()
-                        .strip_prefix(&self.template_path)
-                        .unwrap_or_else(|_| entry.path()),
-                )
-            });
+        let entries = self.layers().flat_map(|(template_path, template)| {
+            self.template_entries(template_path, template, &excludes)
+                .map(move |entry| entry.map(|entry| (template_path, entry)))
+        });
+        // The files of a template override the ones of its parents at the same rendered path
+        let mut generated = HashSet::new();
 
         let cyan = Style::new().cyan();
         println!("{} {}", Emoji("🔄", ""), cyan.apply_to("Templating files…"),);
         for entry in entries {
-            let entry = entry.map_err(|e| anyhow!("cannot read entry : {}", e))?;
-            let entry_path = entry.path().strip_prefix(&self.template_path)?;
+            let (template_path, entry) = entry.map_err(|e| anyhow!("cannot read entry : {}", e))?;
+            let entry_path = entry.path().strip_prefix(template_path)?;
 
-            if entry_path == PathBuf::from("") {
+            if entry_path.as_os_str().is_empty() {
                 continue;
             }
             if entry.file_type().is_dir() {
@@ -493,59 +1104,134 @@ impl ScaffoldDescription {
                 }
 
                 let entry_path = render_path(&template_engine, entry_path, &parameters)?;
+                fs::create_dir_all(dir_path.join(entry_path))
+                    .map_err(|e| anyhow!("cannot create dir : {}", e))?;
+                continue;
+            }
 
-                let dir_path_to_create = dir_path.join(&entry_path);
-                if dir_path_to_create.exists() && self.force {
-                    fs::remove_dir_all(&dir_path_to_create)
-                        .with_context(|| "Cannot remove directory")?;
-                }
-                if dir_path_to_create.exists() && self.append {
+            if entry.path_is_symlink() {
+                if !generated.insert(render_path(&template_engine, entry_path, &parameters)?) {
                     continue;
                 }
-                fs::create_dir(dir_path.join(entry_path))
-                    .map_err(|e| anyhow!("cannot create dir : {}", e))?;
+                self.create_symlink(
+                    &template_engine,
+                    template_path,
+                    &entry,
+                    &dir_path,
+                    &project_path,
+                    &parameters,
+                )?;
                 continue;
             }
 
+            let path = if disable_templating.is_match(entry_path) {
+                entry_path.to_path_buf()
+            } else {
+                render_path(&template_engine, entry_path, &parameters)?
+            };
+            if !generated.insert(path.clone()) {
+                continue;
+            }
             let filename = entry.path();
             let mut content = Vec::new();
             {
                 let mut file =
                     File::open(filename).map_err(|e| anyhow!("cannot open file : {}", e))?;
-                // TODO add the ability to read a non string file
                 file.read_to_end(&mut content)
                     .map_err(|e| anyhow!("cannot read file {filename:?} : {}", e))?;
             }
-            let (path, content) = if disable_templating.is_match(entry_path) {
-                (dir_path.join(entry_path), content)
+            let content = if disable_templating.is_match(entry_path) {
+                content
+            } else if is_binary(&content) {
+                if self.warn_binary {
+                    println!(
+                        "{} {}",
+                        Emoji("⚠️", ""),
+                        Style::new().yellow().apply_to(format!(
+                            "{} is a binary file, it is copied without templating",
+                            entry_path.to_string_lossy()
+                        ))
+                    );
+                }
+                content
             } else {
-                let content = std::str::from_utf8(&content)
-                    .map_err(|_| anyhow!("invalid UTF-8 in {entry_path:?}, consider disabling templating for this file"))?;
-                let rendered_content = template_engine
-                    .render_template(content, &parameters)
-                    .map_err(|e| anyhow!("cannot render template {entry_path:?} : {}", e))?;
-
-                let rendered_path =
-                    render_path(&template_engine, &dir_path.join(entry_path), &parameters)?;
-                (rendered_path, rendered_content.into_bytes())
+                match String::from_utf8(content) {
+                    Ok(content) => {
+                        let content = with_delimiters(&content, entry_path, &delimiters);
+                        let rendered_content = template_engine
+                            .render_template(&content, &parameters)
+                            .map_err(|e| {
+                                anyhow!("cannot render template {entry_path:?} : {}", e)
+                            })?;
+                        rendered_content.into_bytes()
+                    }
+                    Err(e) => {
+                        // Text in another encoding, like Latin-1, cannot be rendered
+                        println!(
+                            "{} {}",
+                            Emoji("⚠️", ""),
+                            Style::new().yellow().apply_to(format!(
+                                "{} is not encoded in UTF-8, it is copied without templating",
+                                entry_path.to_string_lossy()
+                            ))
+                        );
+                        e.into_bytes()
+                    }
+                }
             };
 
-            let filename_path = PathBuf::from(&path);
-            // We skip the file if the file already exist and if we are in an append mode
-            if filename_path.exists() && !self.force && self.append {
-                continue;
-            }
+            // When appending, the user decides what to do with the files which already exist
+            let existing_path = project_path.join(&path);
+            let (path, content) = if mode == Mode::Merge && existing_path.exists() {
+                let protected = protected.iter().any(|p| path.starts_with(p));
+                if protected || !existing_path.is_file() {
+                    conflicts.kept.push(path);
+                    continue;
+                }
+                let existing = fs::read(&existing_path)
+                    .map_err(|e| anyhow!("cannot read file {existing_path:?} : {}", e))?;
+                if existing == content {
+                    continue;
+                }
+                match conflict::resolve(&path, &existing, &content, self.on_conflict)? {
+                    Resolution::Keep => {
+                        conflicts.kept.push(path);
+                        continue;
+                    }
+                    Resolution::Overwrite => (path, content),
+                    Resolution::Rename => {
+                        let path = conflict::new_file_path(&path);
+                        conflicts.renamed.push(path.clone());
+                        (path, content)
+                    }
+                    Resolution::Merge(merged) => {
+                        conflicts.merged.push(path.clone());
+                        (path, merged)
+                    }
+                }
+            } else {
+                (path, content)
+            };
 
-            let permissions = entry
-                .metadata()
-                .map_err(|e| anyhow!("cannot get metadata for path : {}", e))?
-                .permissions();
+            let permissions = self.permissions(&entry, entry_path, &modes)?;
 
-            let mut file = OpenOptions::new().write(true).create(true).open(&path)?;
-            file.set_permissions(permissions)
-                .map_err(|e| anyhow!("cannot set permission to file {:?} : {}", path, e))?;
-            file.write_all(&content)
-                .map_err(|e| anyhow!("cannot create file : {}", e))?;
+            write_file(&dir_path.join(path), &content, permissions)?;
+        }
+        self.apply_edits(
+            &template_engine,
+            &parameters,
+            (&dir_path, &project_path),
+            mode,
+            &protected,
+        )?;
+        transaction.commit()?;
+        if self.workspace {
+            if let Err(e) =
+                self.register_in_workspace(workspace.as_ref(), &project_path, &mut transaction)
+            {
+                transaction.rollback()?;
+                return Err(e);
+            }
         }
 
         let green = Style::new().green();
@@ -555,6 +1241,7 @@ impl ScaffoldDescription {
             green.apply_to(name),
             Emoji("🚀", "")
         );
+        conflicts.print();
 
         let yellow = Style::new().yellow();
         println!(
@@ -586,23 +1273,503 @@ impl ScaffoldDescription {
                     Emoji("🤖", ""),
                     cyan.apply_to("Triggering post-hooks…"),
                 );
-                let commands = commands
-                    .iter()
-                    .map(|c| template_engine.render_template(c, &parameters).ok())
-                    .map(|v| v.unwrap())
-                    .collect::<Vec<String>>();
+                let result = render_hooks(&template_engine, commands, &parameters)
+                    .and_then(|commands| self.run_hooks(&project_path, &commands));
+                if let Err(e) = result {
+                    if self.strict_hooks {
+                        println!(
+                            "{} {}",
+                            Emoji("⏪", ""),
+                            yellow.apply_to("A post-hook failed, rolling back the generation…"),
+                        );
+                        transaction.rollback()?;
+                    } else {
+                        transaction.finish()?;
+                    }
+                    return Err(e);
+                }
+            }
+        }
+
+        transaction.finish()
+    }
 
-                self.run_hooks(&dir_path, &commands)?;
+    /// Insert the contents of the injections and apply the patches to the staged files,
+    /// or to the files of the existing project directory which are then staged
+    fn apply_edits(
+        &self,
+        template_engine: &Handlebars,
+        parameters: &IndexMap<String, Value>,
+        dirs: (&Path, &Path),
+        mode: Mode,
+        protected: &[PathBuf],
+    ) -> Result<()> {
+        if self.inject.is_empty() && self.patch.is_empty() {
+            return Ok(());
+        }
+        let cyan = Style::new().cyan();
+        println!("{} {}", Emoji("💉", ""), cyan.apply_to("Editing files…"));
+        let render_file = |file: &str| {
+            let file = template_engine
+                .render_template(file, parameters)
+                .map(PathBuf::from)
+                .map_err(|e| anyhow!("cannot render template for path : {}", e))?;
+            if !is_project_relative(&file) {
+                return Err(anyhow!(
+                    "cannot edit {file:?}, it must be relative to the project directory, without `..`"
+                ));
             }
+            Ok(file)
+        };
+
+        for injection in &self.inject {
+            let file = render_file(&injection.file)?;
+            let content = template_engine
+                .render_template(&injection.content, parameters)
+                .map_err(|e| anyhow!("cannot render injection into {file:?} : {}", e))?;
+            edit_file(&file, dirs, mode, protected, |file_content| {
+                inject::inject(file_content, &content, injection)
+            })
+            .map_err(|e| anyhow!("cannot inject into {file:?} : {}", e))?;
+        }
+
+        for patch in &self.patch {
+            let file = render_file(&patch.file)?;
+            let format = patch::Format::of(&file).ok_or_else(|| {
+                anyhow!("cannot patch {file:?}, only TOML and JSON files can be patched")
+            })?;
+            let key = template_engine
+                .render_template(&patch.key, parameters)
+                .map_err(|e| anyhow!("cannot render patch key : {}", e))
+                .and_then(|key| patch::parse_key(&key))?;
+            let value = patch::render_value(template_engine, &patch.value, parameters)?;
+            edit_file(&file, dirs, mode, protected, |file_content| {
+                patch::patch(format, file_content, patch.op, &key, &value)
+            })
+            .map_err(|e| anyhow!("cannot patch {file:?} : {}", e))?;
         }
 
         Ok(())
     }
 
-    fn run_hooks(&self, project_path: &Path, commands: &[String]) -> Result<()> {
-        let initial_path = std::env::current_dir()?;
-        // move to project directory
-        std::env::set_current_dir(project_path).map_err(|e| {
+    /// Add the generated crate to the members of the enclosing workspace, unless it's already
+    /// one of them or excluded
+    fn register_in_workspace(
+        &self,
+        workspace: Option<&Workspace>,
+        project_path: &Path,
+        transaction: &mut Transaction,
+    ) -> Result<()> {
+        let yellow = Style::new().yellow();
+        let warn = |message: String| println!("{} {}", Emoji("⚠️", ""), yellow.apply_to(message));
+        let Some(workspace) = workspace else {
+            warn("No cargo workspace encloses the project, it is not registered".to_string());
+            return Ok(());
+        };
+        let relative_path = match workspace.relative_path(project_path) {
+            Some(path) if project_path.join("Cargo.toml").is_file() => path,
+            _ => {
+                warn(
+                    "The project is not a crate, it is not registered in the workspace".to_string(),
+                );
+                return Ok(());
+            }
+        };
+
+        let manifest_path = workspace.manifest_path();
+        match workspace.membership(&relative_path)? {
+            Membership::Member => Ok(()),
+            Membership::Excluded => {
+                warn(format!(
+                    "{relative_path} is excluded from the workspace, it is not registered"
+                ));
+                Ok(())
+            }
+            Membership::Missing(manifest) => {
+                let cyan = Style::new().cyan();
+                println!(
+                    "{} {}",
+                    Emoji("📦", ""),
+                    cyan.apply_to(format!(
+                        "Registering {relative_path} in the members of {}…",
+                        manifest_path.to_string_lossy()
+                    )),
+                );
+                transaction.edit_outside(&manifest_path, workspace.manifest(), &manifest)
+            }
+        }
+    }
+
+    /// Entries of the existing project directory matching the protected globs, relative to it
+    fn protected_paths(&self, project_path: &Path) -> Result<Vec<PathBuf>> {
+        let protected = build_globset(self.template.protected.as_deref())?;
+        if protected.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut paths = Vec::new();
+        let mut entries = WalkDir::new(project_path).min_depth(1).into_iter();
+        while let Some(entry) = entries.next() {
+            let entry = entry.map_err(|e| anyhow!("cannot read entry : {}", e))?;
+            let path = entry.path().strip_prefix(project_path)?;
+            if protected.is_match(path) {
+                // A protected directory is kept with all its content
+                if entry.file_type().is_dir() {
+                    entries.skip_current_dir();
+                }
+                paths.push(path.to_path_buf());
+            }
+        }
+
+        Ok(paths)
+    }
+
+    /// Explicit modes with the glob selecting their files
+    fn modes(&self) -> Result<Vec<(GlobMatcher, u32)>> {
+        self.template
+            .modes
+            .iter()
+            .flatten()
+            .map(|mode| {
+                let glob = Glob::new(mode.glob.trim_start_matches("./"))?;
+                Ok((glob.compile_matcher(), mode.mode))
+            })
+            .collect()
+    }
+
+    /// Permissions of a generated file: the mode of the first glob matching it, or the mode of
+    /// the template file with the umask applied
+    fn permissions(
+        &self,
+        entry: &walkdir::DirEntry,
+        entry_path: &Path,
+        modes: &[(GlobMatcher, u32)],
+    ) -> Result<fs::Permissions> {
+        #[cfg_attr(not(unix), allow(unused_mut))]
+        let mut permissions = entry
+            .metadata()
+            .map_err(|e| anyhow!("cannot get metadata for path : {}", e))?
+            .permissions();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let mode = match modes.iter().find(|(glob, _)| glob.is_match(entry_path)) {
+                Some((_, mode)) => *mode,
+                None => permissions.mode() & !self.umask.unwrap_or_default(),
+            };
+            permissions.set_mode(mode & 0o7777);
+        }
+        #[cfg(not(unix))]
+        let _ = (entry_path, modes);
+
+        Ok(permissions)
+    }
+
+    /// Recreate a symlink of the template in the generated project, with its target rendered
+    fn create_symlink(
+        &self,
+        template_engine: &Handlebars,
+        template_path: &Path,
+        entry: &walkdir::DirEntry,
+        dir_path: &Path,
+        project_path: &Path,
+        parameters: &IndexMap<String, Value>,
+    ) -> Result<()> {
+        let entry_path = entry.path().strip_prefix(template_path)?;
+        let template_target = fs::read_link(entry.path())
+            .map_err(|e| anyhow!("cannot read symlink {entry_path:?} : {}", e))?;
+        let target = render_path(template_engine, &template_target, parameters)?;
+        let link_path = render_path(template_engine, entry_path, parameters)?;
+        if link_escapes(&link_path, &target) {
+            return Err(anyhow!(
+                "the symlink {entry_path:?} points to {target:?}, outside of the template"
+            ));
+        }
+
+        // Like files, existing symlinks are kept when appending
+        if self.append
+            && !self.force
+            && !self.overwrite
+            && project_path.join(&link_path).symlink_metadata().is_ok()
+        {
+            return Ok(());
+        }
+        let path = dir_path.join(&link_path);
+        if path.symlink_metadata().is_ok() {
+            if path.is_dir() && !path.is_symlink() {
+                fs::remove_dir_all(&path)
+            } else {
+                fs::remove_file(&path)
+            }
+            .map_err(|e| anyhow!("cannot remove {path:?} : {}", e))?;
+        }
+
+        #[cfg(unix)]
+        let created = std::os::unix::fs::symlink(&target, &path);
+        #[cfg(windows)]
+        let created = {
+            let template_link_dir = entry.path().parent().unwrap_or(template_path);
+            if template_link_dir.join(&template_target).is_dir() {
+                std::os::windows::fs::symlink_dir(&target, &path)
+            } else {
+                std::os::windows::fs::symlink_file(&target, &path)
+            }
+        };
+        created.map_err(|e| anyhow!("cannot create symlink {path:?} : {}", e))
+    }
+
+    /// Custom delimiters with the glob selecting their files
+    fn delimiters(&self) -> Result<Vec<(GlobMatcher, &Delimiters)>> {
+        self.template
+            .delimiters
+            .iter()
+            .flatten()
+            .map(|delimiters| {
+                let glob = Glob::new(delimiters.glob.trim_start_matches("./"))?;
+                Ok((glob.compile_matcher(), delimiters))
+            })
+            .collect()
+    }
+
+    fn template_engine(&self) -> Result<Handlebars<'static>> {
+        let mut template_engine = Handlebars::new();
+        #[cfg(feature = "helpers")]
+        handlebars_misc_helpers::setup_handlebars(&mut template_engine);
+        template_engine.register_helper("forRange", Box::new(ForRangHelper));
+        // after the helpers setup, which enables the strict mode
+        template_engine.set_strict_mode(self.strict);
+        let disable_templating = build_globset(self.template.disable_templating.as_deref())?;
+        let delimiters = self.delimiters()?;
+        for (name, template_path, partial_path) in self.partials()? {
+            let content = fs::read_to_string(template_path.join(&partial_path))
+                .map_err(|e| anyhow!("cannot read partial {partial_path:?} : {}", e))?;
+            // Converted like the files, given the path of the partial
+            let content = if disable_templating.is_match(&partial_path) {
+                content.replace("{{", "\\{{")
+            } else {
+                with_delimiters(&content, &partial_path, &delimiters).into_owned()
+            };
+            template_engine
+                .register_partial(&name, content)
+                .map_err(|e| anyhow!("cannot register partial {partial_path:?} : {}", e))?;
+        }
+
+        let script_helpers = self.script_helpers()?;
+        #[cfg(feature = "script_helpers")]
+        {
+            // before registering the scripts, which are compiled by the engine
+            template_engine.set_engine(helpers::script_engine());
+            for (name, path) in script_helpers {
+                template_engine
+                    .register_script_helper_file(&name, &path)
+                    .map_err(|e| anyhow!("cannot register script helper {path:?} : {}", e))?;
+            }
+        }
+        #[cfg(not(feature = "script_helpers"))]
+        if let Some((_, path)) = script_helpers.first() {
+            return Err(anyhow!(
+                "cannot register script helper {path:?} : cargo-scaffold is built without the script_helpers feature"
+            ));
+        }
+
+        Ok(template_engine)
+    }
+
+    /// Rhai scripts of the helpers directories of the template and of its parents, named after
+    /// their file stem. The helpers of a template override the ones of its parents.
+    fn script_helpers(&self) -> Result<Vec<(String, PathBuf)>> {
+        let mut helpers: Vec<(String, PathBuf)> = Vec::new();
+        for (template_path, template) in self.layers() {
+            let helpers_path = template_path.join(template.helpers_dir());
+            if !helpers_path.is_dir() {
+                continue;
+            }
+            for entry in WalkDir::new(&helpers_path).max_depth(1).sort_by_file_name() {
+                let entry = entry.map_err(|e| anyhow!("cannot read entry : {}", e))?;
+                let path = entry.path();
+                if !entry.file_type().is_file() || !is_script_helper(path) {
+                    continue;
+                }
+                let Some(name) = path.file_stem().and_then(|n| n.to_str()) else {
+                    continue;
+                };
+                if !helpers.iter().any(|(registered, _)| registered == name) {
+                    helpers.push((name.to_string(), path.to_path_buf()));
+                }
+            }
+        }
+
+        Ok(helpers)
+    }
+
+    /// Partials of the template and of its parents, named after their path in the partials
+    /// directory without extension (i.e.: `rust/imports` for `partials/rust/imports.rs`).
+    /// The partials of a template override the ones of its parents with the same name.
+    /// Returns their name, with the template directory and their path relative to it.
+    fn partials(&self) -> Result<Vec<(String, &Path, PathBuf)>> {
+        let mut partials: Vec<(String, &Path, PathBuf)> = Vec::new();
+        for (template_path, template) in self.layers() {
+            let partials_dir = template.partials_dir();
+            let partials_path = template_path.join(partials_dir);
+            if !partials_path.is_dir() {
+                continue;
+            }
+            for entry in WalkDir::new(&partials_path).sort_by_file_name() {
+                let entry = entry.map_err(|e| anyhow!("cannot read entry : {}", e))?;
+                if !entry.file_type().is_file() {
+                    continue;
+                }
+                let partial_path = entry.path().strip_prefix(template_path)?;
+                let name = partial_path
+                    .strip_prefix(partials_dir)?
+                    .with_extension("")
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                if !partials.iter().any(|(registered, ..)| *registered == name) {
+                    partials.push((name, template_path, partial_path.to_path_buf()));
+                }
+            }
+        }
+
+        Ok(partials)
+    }
+
+    /// List entries inside the directory of the template or of one of its parents
+    fn template_entries<'a>(
+        &'a self,
+        template_path: &'a Path,
+        template: &'a TemplateDescription,
+        excludes: &'a GlobSet,
+    ) -> impl Iterator<Item = walkdir::Result<walkdir::DirEntry>> + 'a {
+        WalkDir::new(template_path)
+            .into_iter()
+            .filter_entry(move |entry| {
+                // Do not include git files
+                if entry
+                    .path()
+                    .components()
+                    .any(|c| c == std::path::Component::Normal(".git".as_ref()))
+                {
+                    return false;
+                }
+
+                if entry.depth() == 1 && entry.file_name() == SCAFFOLD_FILENAME {
+                    return false;
+                }
+
+                let entry_path = entry
+                    .path()
+                    .strip_prefix(template_path)
+                    .unwrap_or_else(|_| entry.path());
+                // Only the scripts of the helpers directory are not copied, and the directory
+                // itself when it has nothing else
+                let helpers_dir = template.helpers_dir();
+                let is_script = entry_path.parent() == Some(helpers_dir)
+                    && entry.file_type().is_file()
+                    && is_script_helper(entry.path());
+                let is_helpers_dir = entry_path == helpers_dir
+                    && fs::read_dir(entry.path()).is_ok_and(|mut entries| {
+                        entries.all(|entry| {
+                            entry.is_ok_and(|entry| {
+                                entry.file_type().is_ok_and(|t| t.is_file())
+                                    && is_script_helper(&entry.path())
+                            })
+                        })
+                    });
+                entry_path != template.partials_dir()
+                    && !is_script
+                    && !is_helpers_dir
+                    && !excludes.is_match(entry_path)
+            })
+    }
+
+    /// Look for references to undeclared parameters and for declared parameters never used
+    /// in the templated files, paths, notes and hooks
+    pub fn lint(&self) -> Result<Vec<LintIssue>> {
+        let excludes = build_globset(self.template.exclude.as_deref())?;
+        let disable_templating = build_globset(self.template.disable_templating.as_deref())?;
+        let delimiters = self.delimiters()?;
+
+        let mut linter = Linter::default();
+        if let Some(notes) = &self.template.notes {
+            linter.check_inline(Path::new(SCAFFOLD_FILENAME), notes);
+        }
+        if let Some(hooks) = &self.hooks {
+            let commands = hooks.pre.iter().chain(hooks.post.iter()).flatten();
+            for command in commands {
+                linter.check_inline(Path::new(SCAFFOLD_FILENAME), command);
+            }
+        }
+        for injection in &self.inject {
+            linter.check_inline(Path::new(SCAFFOLD_FILENAME), &injection.file);
+            linter.check_inline(Path::new(SCAFFOLD_FILENAME), &injection.content);
+        }
+        for patch in &self.patch {
+            for source in [&patch.file, &patch.key, &patch.value.to_string()] {
+                linter.check_inline(Path::new(SCAFFOLD_FILENAME), source);
+            }
+        }
+
+        for (_, template_path, partial_path) in self.partials()? {
+            if disable_templating.is_match(&partial_path) {
+                continue;
+            }
+            let content = fs::read(template_path.join(&partial_path))
+                .map_err(|e| anyhow!("cannot read partial {partial_path:?} : {}", e))?;
+            if let Ok(content) = std::str::from_utf8(&content) {
+                linter.check(
+                    &partial_path,
+                    &with_delimiters(content, &partial_path, &delimiters),
+                );
+            }
+        }
+
+        let entries = self.layers().flat_map(|(template_path, template)| {
+            self.template_entries(template_path, template, &excludes)
+                .map(move |entry| entry.map(|entry| (template_path, entry)))
+        });
+        for entry in entries {
+            let (template_path, entry) = entry.map_err(|e| anyhow!("cannot read entry : {}", e))?;
+            let entry_path = entry.path().strip_prefix(template_path)?;
+            if let Some(file_name) = entry_path.file_name().and_then(|f| f.to_str()) {
+                linter.check_inline(entry_path, file_name);
+            }
+            if entry.path_is_symlink() {
+                let target = fs::read_link(entry.path())
+                    .map_err(|e| anyhow!("cannot read symlink {entry_path:?} : {}", e))?;
+                linter.check_inline(entry_path, &target.to_string_lossy());
+            }
+            if !entry.file_type().is_file() || disable_templating.is_match(entry_path) {
+                continue;
+            }
+            let content = fs::read(entry.path())
+                .map_err(|e| anyhow!("cannot read file {entry_path:?} : {}", e))?;
+            if let Ok(content) = std::str::from_utf8(&content) {
+                linter.check(
+                    entry_path,
+                    &with_delimiters(content, entry_path, &delimiters),
+                );
+            }
+        }
+
+        // script helpers without parameters look like variables
+        let script_helpers = self.script_helpers()?;
+        let declared = self
+            .parameters
+            .keys()
+            .map(String::as_str)
+            .chain(validate::RESERVED_PARAMETERS.iter().copied())
+            .chain(script_helpers.iter().map(|(name, _)| name.as_str()));
+        Ok(linter.issues(declared, self.parameters.keys().map(String::as_str)))
+    }
+
+    fn run_hooks(&self, project_path: &Path, commands: &[String]) -> Result<()> {
+        let initial_path = std::env::current_dir()?;
+        // move to project directory
+        std::env::set_current_dir(project_path).map_err(|e| {
             anyhow!(
                 "cannot change directory to project path {:?}: {}",
                 &project_path,
@@ -611,11 +1778,17 @@ impl ScaffoldDescription {
         })?;
         // run commands
         let magenta = Style::new().magenta();
-        for cmd in commands {
+        let result = commands.iter().try_for_each(|cmd| {
             println!("{} {}", Emoji("✨", ""), magenta.apply_to(cmd));
-            ScaffoldDescription::run_cmd(cmd)?;
-        }
-        // move back to initial path
+            let status = ScaffoldDescription::setup_cmd(cmd)?
+                .status()
+                .map_err(|e| anyhow!("cannot execute hook {cmd:?} : {}", e))?;
+            if self.strict_hooks && !status.success() {
+                return Err(anyhow!("hook {cmd:?} failed: {status}"));
+            }
+            Ok(())
+        });
+        // move back to initial path, even if a hook failed
         std::env::set_current_dir(&initial_path).map_err(|e| {
             anyhow!(
                 "cannot move back to original path {:?}: {}",
@@ -623,7 +1796,7 @@ impl ScaffoldDescription {
                 e
             )
         })?;
-        Ok(())
+        result
     }
 
     pub fn run_cmd(cmd: &str) -> Result<()> {
@@ -647,6 +1820,196 @@ impl ScaffoldDescription {
     }
 }
 
+/// Render the commands of a hook, failing on the first one referencing an undefined variable
+/// in strict mode
+fn render_hooks(
+    template_engine: &Handlebars,
+    commands: &[String],
+    parameters: &IndexMap<String, Value>,
+) -> Result<Vec<String>> {
+    commands
+        .iter()
+        .map(|command| {
+            template_engine
+                .render_template(command, parameters)
+                .map_err(|e| anyhow!("cannot render hook {command:?} : {}", e))
+        })
+        .collect()
+}
+
+/// Edit a staged file, or a file of the existing project directory which is then staged
+fn edit_file(
+    file: &Path,
+    (dir_path, project_path): (&Path, &Path),
+    mode: Mode,
+    protected: &[PathBuf],
+    edit: impl FnOnce(&str) -> Result<Option<String>>,
+) -> Result<()> {
+    let staged_path = dir_path.join(file);
+    let existing_path = project_path.join(file);
+    let source = if staged_path.is_file() {
+        staged_path.clone()
+    } else if matches!(mode, Mode::Merge | Mode::Overwrite) && existing_path.is_file() {
+        if protected.iter().any(|p| file.starts_with(p)) {
+            println!(
+                "{} {}",
+                Emoji("⚠️", ""),
+                Style::new().yellow().apply_to(format!(
+                    "{} is protected, it is not edited",
+                    file.to_string_lossy()
+                ))
+            );
+            return Ok(());
+        }
+        existing_path
+    } else {
+        return Err(anyhow!("the file doesn't exist"));
+    };
+
+    let content = fs::read_to_string(&source).map_err(|e| anyhow!("cannot read file : {}", e))?;
+    if let Some(edited) = edit(&content)? {
+        let permissions = fs::metadata(&source)
+            .map_err(|e| anyhow!("cannot get metadata for path : {}", e))?
+            .permissions();
+        if let Some(parent) = staged_path.parent() {
+            fs::create_dir_all(parent).map_err(|e| anyhow!("cannot create dir : {}", e))?;
+        }
+        write_file(&staged_path, edited.as_bytes(), permissions)?;
+    }
+
+    Ok(())
+}
+
+/// Ask the user to confirm the replacement of a git repository with uncommitted changes,
+/// refused when not running in a terminal
+fn confirm_dirty_replace(dir_path: &Path) -> Result<bool> {
+    if !console::user_attended() {
+        return Ok(false);
+    }
+    Ok(Confirm::new()
+        .with_prompt(format!(
+            "{} has uncommitted changes in its git repository, replace it anyway ?",
+            dir_path.to_string_lossy()
+        ))
+        .default(false)
+        .interact()?)
+}
+
+/// Write the file to a temporary file next to it, renamed into place once complete,
+/// so a file is never partially written nor mixed with its previous content
+fn write_file(path: &Path, content: &[u8], permissions: fs::Permissions) -> Result<()> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut file = tempfile::NamedTempFile::new_in(dir)
+        .map_err(|e| anyhow!("cannot create temporary file in {dir:?} : {}", e))?;
+    file.write_all(content)
+        .map_err(|e| anyhow!("cannot create file : {}", e))?;
+    // once written, as the file can be read-only
+    file.as_file()
+        .set_permissions(permissions)
+        .map_err(|e| anyhow!("cannot set permission to file {:?} : {}", path, e))?;
+    file.persist(path)
+        .map_err(|e| anyhow!("cannot create file {:?} : {}", path, e.error))?;
+
+    Ok(())
+}
+
+/// Whether a path is relative to the project directory and stays inside of it, edited files
+/// must not be elsewhere
+pub(crate) fn is_project_relative(path: &Path) -> bool {
+    path.components().all(|component| {
+        matches!(
+            component,
+            std::path::Component::Normal(_) | std::path::Component::CurDir
+        )
+    })
+}
+
+/// Whether the target of a symlink, relative to the directory of the link, is outside of the
+/// generated project
+fn link_escapes(link: &Path, target: &Path) -> bool {
+    let mut depth = link.components().count() as isize - 1;
+    for component in target.components() {
+        match component {
+            std::path::Component::ParentDir => depth -= 1,
+            std::path::Component::Normal(_) => depth += 1,
+            std::path::Component::CurDir => {}
+            // absolute target
+            std::path::Component::RootDir | std::path::Component::Prefix(_) => return true,
+        }
+        if depth < 0 {
+            return true;
+        }
+    }
+
+    false
+}
+
+/// Items of both lists, the first ones first
+fn concat<T>(first: Option<Vec<T>>, second: Option<Vec<T>>) -> Option<Vec<T>> {
+    match (first, second) {
+        (Some(mut first), Some(second)) => {
+            first.extend(second);
+            Some(first)
+        }
+        (first, second) => first.or(second),
+    }
+}
+
+fn parse_octal(mode: &str) -> Result<u32, String> {
+    u32::from_str_radix(mode.trim_start_matches("0o"), 8)
+        .map_err(|_| format!("invalid octal mode {mode:?}"))
+}
+
+/// Binary files, like images, fonts or archives, are copied without templating
+pub(crate) fn is_binary(content: &[u8]) -> bool {
+    content_inspector::inspect(content).is_binary()
+}
+
+/// Rhai scripts of a helpers directory are registered as helpers
+fn is_script_helper(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()) == Some("rhai")
+}
+
+/// Convert the content of a file using custom delimiters to the handlebars syntax,
+/// escaping its braces
+fn with_delimiters<'a>(
+    content: &'a str,
+    path: &Path,
+    delimiters: &[(GlobMatcher, &Delimiters)],
+) -> Cow<'a, str> {
+    let Some((_, Delimiters { open, close, .. })) =
+        delimiters.iter().find(|(glob, _)| glob.is_match(path))
+    else {
+        return Cow::Borrowed(content);
+    };
+
+    let mut output = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(start) = rest.find(open.as_str()) {
+        let expression = &rest[start + open.len()..];
+        let Some(end) = expression.find(close.as_str()) else {
+            break;
+        };
+        output.push_str(&rest[..start].replace("{{", "\\{{"));
+        output.push_str("{{");
+        output.push_str(&expression[..end]);
+        output.push_str("}}");
+        rest = &expression[end + close.len()..];
+    }
+    output.push_str(&rest.replace("{{", "\\{{"));
+
+    Cow::Owned(output)
+}
+
+fn build_globset(patterns: Option<&[String]>) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns.unwrap_or_default() {
+        builder.add(Glob::new(pattern.trim_start_matches("./"))?);
+    }
+
+    Ok(builder.build()?)
+}
+
 fn render_path(
     template_engine: &Handlebars,
     path: &Path,
@@ -673,6 +2036,20 @@ fn render_path(
     Ok(output)
 }
 
+impl TemplateDescription {
+    /// Path of the partials directory, relative to the template directory
+    fn partials_dir(&self) -> &Path {
+        let partials = self.partials.as_deref().unwrap_or(PARTIALS_DIR);
+        Path::new(partials.trim_start_matches("./"))
+    }
+
+    /// Path of the script helpers directory, relative to the template directory
+    fn helpers_dir(&self) -> &Path {
+        let helpers = self.helpers.as_deref().unwrap_or(HELPERS_DIR);
+        Path::new(helpers.trim_start_matches("./"))
+    }
+}
+
 impl Parameter {
     fn to_value_interactive(&self) -> Result<toml::Value> {
         let value = match self.r#type {
@@ -735,15 +2112,18 @@ impl Parameter {
 
 #[cfg(test)]
 mod tests {
-    use crate::{render_path, Handlebars};
+    use crate::{is_binary, render_path, write_file, Handlebars};
     use indexmap::IndexMap;
 
-    use super::{Opts, ScaffoldDescription};
+    use super::{ConflictPolicy, Opts, ScaffoldDescription};
     use std::fs::{remove_file, File};
     use std::io::Write;
     use std::path::Path;
     use std::process::{Command, Stdio};
 
+    /// Tests depending on the current directory, which is changed while running hooks
+    static CURRENT_DIR: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
     #[test]
     #[cfg(windows)]
     fn windows_paths_interpolation_works() {
@@ -838,6 +2218,1105 @@ mod tests {
         remove_file(script_name).unwrap();
     }
 
+    #[test]
+    fn template_metadata() {
+        let template_dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            template_dir.path().join(".scaffold.toml"),
+            r#"
+            [template]
+            name = "service"
+            author = "Benjamin Coenen"
+            version = "1.2.0"
+            description = "An HTTP service"
+            homepage = "https://github.com/username/service"
+            min_scaffold_version = "0.1"
+            "#,
+        )
+        .unwrap();
+
+        let desc = ScaffoldDescription::new(Opts::builder(template_dir.path())).unwrap();
+        assert_eq!(desc.template_name(), Some("service"));
+        assert_eq!(desc.template_author(), Some("Benjamin Coenen"));
+        assert_eq!(desc.template_version(), Some("1.2.0"));
+        assert_eq!(desc.template_description(), Some("An HTTP service"));
+        assert_eq!(
+            desc.template_homepage(),
+            Some("https://github.com/username/service")
+        );
+        assert_eq!(desc.min_scaffold_version(), Some("0.1"));
+    }
+
+    #[test]
+    fn min_scaffold_version_is_enforced() {
+        let template_dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            template_dir.path().join(".scaffold.toml"),
+            "[template]\nmin_scaffold_version = \"999.0.0\"\n",
+        )
+        .unwrap();
+
+        let err = ScaffoldDescription::new(Opts::builder(template_dir.path()))
+            .err()
+            .unwrap();
+        assert!(err
+            .to_string()
+            .contains("requires cargo-scaffold 999.0.0 or newer"));
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        let template_dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            template_dir.path().join(".scaffold.toml"),
+            "[template]\ndisable_templatng = [\"*.png\"]\n",
+        )
+        .unwrap();
+
+        let err = ScaffoldDescription::new(Opts::builder(template_dir.path()))
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains(
+            ".scaffold.toml:2:1: unknown key `disable_templatng` in [template], did you mean `disable_templating`?"
+        ));
+
+        assert!(ScaffoldDescription::new(
+            Opts::builder(template_dir.path()).allow_unknown_keys(true)
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn lint_and_strict_mode() {
+        let template_dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            template_dir.path().join(".scaffold.toml"),
+            r#"
+            [template]
+            notes = "Run {{name}}"
+
+            [parameters.description]
+            type = "string"
+            message = "Description"
+
+            [parameters.feature]
+            type = "string"
+            message = "Feature"
+            "#,
+        )
+        .unwrap();
+        std::fs::write(
+            template_dir.path().join("{{feature}}.md"),
+            "# {{name}}\n{{descripton}}\n",
+        )
+        .unwrap();
+
+        let desc = ScaffoldDescription::new(Opts::builder(template_dir.path())).unwrap();
+        let issues = desc
+            .lint()
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            issues,
+            vec![
+                "{{feature}}.md:2:1: reference to undeclared parameter `descripton`",
+                ".scaffold.toml: parameter `description` is never used",
+            ]
+        );
+
+        let parameters = IndexMap::from([("feature".to_string(), toml::Value::from("api"))]);
+        let target_dir = tempfile::tempdir().unwrap();
+        let opts = || {
+            Opts::builder(template_dir.path())
+                .project_name("project")
+                .target_dir(target_dir.path())
+                .append(true)
+        };
+        ScaffoldDescription::new(opts())
+            .unwrap()
+            .scaffold_with_parameters(parameters.clone())
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(target_dir.path().join("api.md")).unwrap(),
+            "# project\n\n"
+        );
+
+        assert!(ScaffoldDescription::new(opts().strict(true))
+            .unwrap()
+            .scaffold_with_parameters(parameters)
+            .is_err());
+    }
+
+    #[test]
+    fn custom_delimiters_and_raw_blocks() {
+        let template_dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            template_dir.path().join(".scaffold.toml"),
+            r#"
+            [template]
+            [[template.delimiters]]
+            glob = "*.yml"
+            open = "<%"
+            close = "%>"
+            "#,
+        )
+        .unwrap();
+        std::fs::create_dir_all(template_dir.path().join(".github").join("workflows")).unwrap();
+        std::fs::write(
+            template_dir
+                .path()
+                .join(".github")
+                .join("workflows")
+                .join("ci.yml"),
+            "name: <% name %>\nrun: echo ${{ github.ref }}\n<%#if name%>on: push<%/if%>\n",
+        )
+        .unwrap();
+        std::fs::write(
+            template_dir.path().join("App.vue"),
+            "<h1>{{name}}</h1>\n{{{{raw}}}}<p>{{ message }}</p>{{{{/raw}}}}\n",
+        )
+        .unwrap();
+
+        let target_dir = tempfile::tempdir().unwrap();
+        ScaffoldDescription::new(
+            Opts::builder(template_dir.path())
+                .project_name("project")
+                .target_dir(target_dir.path())
+                .append(true),
+        )
+        .unwrap()
+        .scaffold_with_parameters(IndexMap::new())
+        .unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(
+                target_dir
+                    .path()
+                    .join(".github")
+                    .join("workflows")
+                    .join("ci.yml")
+            )
+            .unwrap(),
+            "name: project\nrun: echo ${{ github.ref }}\non: push\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(target_dir.path().join("App.vue")).unwrap(),
+            "<h1>project</h1>\n<p>{{ message }}</p>\n"
+        );
+    }
+
+    #[test]
+    fn binary_files_are_copied() {
+        let template_dir = tempfile::tempdir().unwrap();
+        std::fs::write(template_dir.path().join(".scaffold.toml"), "[template]\n").unwrap();
+        let logo = [0x89, b'P', b'N', b'G', 0x00, b'{', b'{', 0xff];
+        std::fs::write(template_dir.path().join("{{name}}.png"), logo).unwrap();
+        std::fs::write(template_dir.path().join("latin1.txt"), [b'{', b'{', 0xe9]).unwrap();
+
+        assert!(is_binary(&logo));
+        assert!(!is_binary("{{name}} ✓".as_bytes()));
+        assert!(!is_binary(&[b'{', b'{', 0xe9]));
+
+        let target_dir = tempfile::tempdir().unwrap();
+        ScaffoldDescription::new(
+            Opts::builder(template_dir.path())
+                .project_name("project")
+                .target_dir(target_dir.path())
+                .append(true)
+                .warn_binary(true),
+        )
+        .unwrap()
+        .scaffold_with_parameters(IndexMap::new())
+        .unwrap();
+
+        assert_eq!(
+            std::fs::read(target_dir.path().join("project.png")).unwrap(),
+            logo
+        );
+        assert_eq!(
+            std::fs::read(target_dir.path().join("latin1.txt")).unwrap(),
+            [b'{', b'{', 0xe9]
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn symlinks_and_modes() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let template_dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            template_dir.path().join(".scaffold.toml"),
+            "[template]\nmodes = [{ glob = \"*.key\", mode = 0o600 }]\n",
+        )
+        .unwrap();
+        std::fs::create_dir(template_dir.path().join("empty")).unwrap();
+        std::fs::create_dir(template_dir.path().join("bin")).unwrap();
+        let script = template_dir.path().join("bin").join("run.sh");
+        std::fs::write(&script, "#!/bin/sh\necho {{name}}\n").unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o775)).unwrap();
+        std::fs::write(template_dir.path().join("{{name}}.key"), "secret").unwrap();
+        symlink("bin/run.sh", template_dir.path().join("run")).unwrap();
+        symlink("{{name}}.key", template_dir.path().join("current.key")).unwrap();
+
+        let target_dir = tempfile::tempdir().unwrap();
+        let opts = || {
+            Opts::builder(template_dir.path())
+                .project_name("project")
+                .target_dir(target_dir.path())
+                .force(true)
+                .umask(0o022)
+        };
+        ScaffoldDescription::new(opts())
+            .unwrap()
+            .scaffold_with_parameters(IndexMap::new())
+            .unwrap();
+
+        let mode = |path: &str| {
+            std::fs::metadata(target_dir.path().join(path))
+                .unwrap()
+                .permissions()
+                .mode()
+                & 0o7777
+        };
+        assert!(target_dir.path().join("empty").is_dir());
+        assert_eq!(mode("bin/run.sh"), 0o755);
+        assert_eq!(mode("project.key"), 0o600);
+        assert_eq!(
+            std::fs::read_link(target_dir.path().join("run")).unwrap(),
+            Path::new("bin/run.sh")
+        );
+        assert_eq!(
+            std::fs::read_link(target_dir.path().join("current.key")).unwrap(),
+            Path::new("project.key")
+        );
+        assert_eq!(
+            std::fs::read_to_string(target_dir.path().join("run")).unwrap(),
+            "#!/bin/sh\necho project\n"
+        );
+
+        symlink(
+            "../../etc/passwd",
+            template_dir.path().join("bin").join("passwd"),
+        )
+        .unwrap();
+        let err = ScaffoldDescription::new(opts())
+            .unwrap()
+            .scaffold_with_parameters(IndexMap::new())
+            .unwrap_err();
+        assert!(err.to_string().contains("outside of the template"));
+    }
+
+    #[test]
+    fn write_file_replaces_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("README.md");
+        std::fs::write(&path, "A much longer previous content\n").unwrap();
+        let mut permissions = std::fs::metadata(&path).unwrap().permissions();
+        permissions.set_readonly(true);
+        std::fs::set_permissions(&path, permissions.clone()).unwrap();
+
+        write_file(&path, b"New\n", permissions).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "New\n");
+        assert!(std::fs::metadata(&path).unwrap().permissions().readonly());
+        // no temporary file is left
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn pre_hooks_run_in_the_project_directory() {
+        // hooks are run from the project directory
+        let _cwd = CURRENT_DIR.lock().unwrap();
+        let template_dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            template_dir.path().join(".scaffold.toml"),
+            "[template]\n[hooks]\npre = [\"cp -n old.txt copy.txt\", \"touch pre.txt\"]\n",
+        )
+        .unwrap();
+        std::fs::write(template_dir.path().join("a.txt"), "{{name}}").unwrap();
+
+        let target = tempfile::tempdir().unwrap();
+        let opts = |project: &Path| {
+            Opts::builder(template_dir.path())
+                .project_name("project")
+                .target_dir(project)
+        };
+
+        // the files created in a new project are kept
+        let created = target.path().join("created");
+        ScaffoldDescription::new(opts(&created))
+            .unwrap()
+            .scaffold_with_parameters(IndexMap::new())
+            .unwrap();
+        assert!(created.join("pre.txt").exists());
+        assert!(created.join("a.txt").exists());
+
+        // the existing files are visible when appending
+        let existing = target.path().join("existing");
+        std::fs::create_dir(&existing).unwrap();
+        std::fs::write(existing.join("old.txt"), "old").unwrap();
+        ScaffoldDescription::new(opts(&existing).append(true))
+            .unwrap()
+            .scaffold_with_parameters(IndexMap::new())
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(existing.join("copy.txt")).unwrap(),
+            "old"
+        );
+        assert!(existing.join("pre.txt").exists());
+        assert!(existing.join("a.txt").exists());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn pre_hooks_target_dir_is_their_directory() {
+        // hooks are run from the project directory
+        let _cwd = CURRENT_DIR.lock().unwrap();
+        let template_dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            template_dir.path().join(".scaffold.toml"),
+            "[template]\n[hooks]\npre = [\"sh -c 'pwd -P > cwd.txt'\", \"sh -c 'cd {{target_dir}} && pwd -P > target.txt'\"]\n",
+        )
+        .unwrap();
+
+        let target = tempfile::tempdir().unwrap();
+        let opts = |project: &Path| {
+            Opts::builder(template_dir.path())
+                .project_name("project")
+                .target_dir(project)
+        };
+
+        // in a new project, the staging directory
+        let created = target.path().join("created");
+        ScaffoldDescription::new(opts(&created))
+            .unwrap()
+            .scaffold_with_parameters(IndexMap::new())
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(created.join("target.txt")).unwrap(),
+            std::fs::read_to_string(created.join("cwd.txt")).unwrap()
+        );
+
+        // when appending, the existing project directory
+        let existing = target.path().join("existing");
+        std::fs::create_dir(&existing).unwrap();
+        ScaffoldDescription::new(opts(&existing).append(true))
+            .unwrap()
+            .scaffold_with_parameters(IndexMap::new())
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(existing.join("target.txt")).unwrap(),
+            format!("{}\n", existing.canonicalize().unwrap().display())
+        );
+        assert_eq!(
+            std::fs::read_to_string(existing.join("cwd.txt")).unwrap(),
+            format!("{}\n", existing.canonicalize().unwrap().display())
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn failed_generation_is_rolled_back() {
+        // hooks are run from the project directory
+        let _cwd = CURRENT_DIR.lock().unwrap();
+        let template_dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            template_dir.path().join(".scaffold.toml"),
+            "[template]\n[hooks]\npost = [\"false\"]\n",
+        )
+        .unwrap();
+        std::fs::write(template_dir.path().join("a.txt"), "{{name}}").unwrap();
+        std::fs::write(template_dir.path().join("b.txt"), "{{#if}}").unwrap();
+
+        let target = tempfile::tempdir().unwrap();
+        let project = target.path().join("project");
+        std::fs::create_dir(&project).unwrap();
+        std::fs::write(project.join("old.txt"), "old").unwrap();
+        let opts = || {
+            Opts::builder(template_dir.path())
+                .project_name("project")
+                .target_dir(&project)
+                .force(true)
+        };
+        let files = || {
+            let mut files = std::fs::read_dir(target.path())
+                .unwrap()
+                .chain(std::fs::read_dir(&project).unwrap())
+                .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+                .collect::<Vec<_>>();
+            files.sort();
+            files
+        };
+
+        // rendering error
+        assert!(ScaffoldDescription::new(opts())
+            .unwrap()
+            .scaffold_with_parameters(IndexMap::new())
+            .is_err());
+        assert_eq!(files(), vec!["old.txt", "project"]);
+
+        // post-hook error
+        std::fs::remove_file(template_dir.path().join("b.txt")).unwrap();
+        assert!(ScaffoldDescription::new(opts().strict_hooks(true))
+            .unwrap()
+            .scaffold_with_parameters(IndexMap::new())
+            .is_err());
+        assert_eq!(files(), vec!["old.txt", "project"]);
+
+        ScaffoldDescription::new(opts())
+            .unwrap()
+            .scaffold_with_parameters(IndexMap::new())
+            .unwrap();
+        assert_eq!(files(), vec!["a.txt", "project"]);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn hooks_referencing_undefined_variables() {
+        // hooks are run from the project directory
+        let _cwd = CURRENT_DIR.lock().unwrap();
+        let template_dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            template_dir.path().join(".scaffold.toml"),
+            "[template]\n[hooks]\npre = [\"echo {{missing}}\"]\n",
+        )
+        .unwrap();
+        std::fs::write(template_dir.path().join("a.txt"), "{{name}}").unwrap();
+
+        let target = tempfile::tempdir().unwrap();
+        let project = target.path().join("project");
+        let opts = || {
+            Opts::builder(template_dir.path())
+                .project_name("project")
+                .target_dir(&project)
+        };
+        let err = ScaffoldDescription::new(opts().strict(true))
+            .unwrap()
+            .scaffold_with_parameters(IndexMap::new())
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("cannot render hook \"echo {{missing}}\" : "));
+        assert!(!project.exists());
+
+        // rendered as empty by default
+        ScaffoldDescription::new(opts())
+            .unwrap()
+            .scaffold_with_parameters(IndexMap::new())
+            .unwrap();
+        assert!(project.join("a.txt").exists());
+    }
+
+    #[test]
+    fn overwrite_keeps_other_and_protected_files() {
+        let template_dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            template_dir.path().join(".scaffold.toml"),
+            "[template]\nprotected = [\".env\", \"target\"]\n",
+        )
+        .unwrap();
+        std::fs::write(template_dir.path().join("README.md"), "# {{name}}").unwrap();
+        std::fs::write(template_dir.path().join(".env"), "SECRET=").unwrap();
+        std::fs::create_dir(template_dir.path().join("target")).unwrap();
+        std::fs::write(template_dir.path().join("target").join("cache"), "").unwrap();
+
+        let target = tempfile::tempdir().unwrap();
+        let project = target.path().join("project");
+        std::fs::create_dir_all(project.join("target")).unwrap();
+        std::fs::write(project.join("README.md"), "old").unwrap();
+        std::fs::write(project.join(".env"), "SECRET=1").unwrap();
+        std::fs::write(project.join("notes.txt"), "").unwrap();
+        let opts = || {
+            Opts::builder(template_dir.path())
+                .project_name("project")
+                .target_dir(&project)
+        };
+        let read = |file: &str| std::fs::read_to_string(project.join(file)).unwrap();
+
+        ScaffoldDescription::new(opts().overwrite(true))
+            .unwrap()
+            .scaffold_with_parameters(IndexMap::new())
+            .unwrap();
+        assert_eq!(read("README.md"), "# project");
+        assert_eq!(read(".env"), "SECRET=1");
+        assert_eq!(read("notes.txt"), "");
+        assert!(!project.join("target").join("cache").exists());
+
+        ScaffoldDescription::new(opts().force(true))
+            .unwrap()
+            .scaffold_with_parameters(IndexMap::new())
+            .unwrap();
+        assert_eq!(read(".env"), "SECRET=1");
+        assert!(!project.join("notes.txt").exists());
+        assert!(!project.join("target").join("cache").exists());
+        assert_eq!(
+            std::fs::read_dir(target.path()).unwrap().count(),
+            1,
+            "no staging nor backup directory left"
+        );
+    }
+
+    #[test]
+    fn append_resolves_conflicts() {
+        let template_dir = tempfile::tempdir().unwrap();
+        std::fs::write(template_dir.path().join(".scaffold.toml"), "[template]\n").unwrap();
+        std::fs::write(template_dir.path().join("main.rs"), "// {{name}}").unwrap();
+        std::fs::write(template_dir.path().join("lib.rs"), "// lib").unwrap();
+
+        let target = tempfile::tempdir().unwrap();
+        let project = target.path().join("project");
+        let scaffold = |policy| {
+            ScaffoldDescription::new(
+                Opts::builder(template_dir.path())
+                    .project_name("project")
+                    .target_dir(&project)
+                    .append(true)
+                    .on_conflict(policy),
+            )
+            .unwrap()
+            .scaffold_with_parameters(IndexMap::new())
+        };
+        let read = |file: &str| std::fs::read_to_string(project.join(file)).unwrap();
+        std::fs::create_dir(&project).unwrap();
+        std::fs::write(project.join("main.rs"), "fn main() {}").unwrap();
+
+        let err = scaffold(ConflictPolicy::Fail).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "cannot generate main.rs because it already exists"
+        );
+        assert!(!project.join("lib.rs").exists());
+
+        scaffold(ConflictPolicy::Skip).unwrap();
+        assert_eq!(read("main.rs"), "fn main() {}");
+        assert_eq!(read("lib.rs"), "// lib");
+
+        scaffold(ConflictPolicy::Rename).unwrap();
+        assert_eq!(read("main.rs"), "fn main() {}");
+        assert_eq!(read("main.rs.scaffold-new"), "// project");
+
+        scaffold(ConflictPolicy::Overwrite).unwrap();
+        assert_eq!(read("main.rs"), "// project");
+    }
+
+    #[test]
+    fn injections_are_applied_once() {
+        let template_dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            template_dir.path().join(".scaffold.toml"),
+            r#"[template]
+
+[[inject]]
+file = "src/lib.rs"
+content = "mod {{module}};"
+after = "^mod "
+
+[[inject]]
+file = "src/{{module}}.rs"
+content = "pub fn {{module}}() {}"
+"#,
+        )
+        .unwrap();
+        std::fs::create_dir(template_dir.path().join("src")).unwrap();
+        std::fs::write(
+            template_dir.path().join("src").join("{{module}}.rs"),
+            "// {{module}}\n",
+        )
+        .unwrap();
+
+        let target = tempfile::tempdir().unwrap();
+        let project = target.path().join("project");
+        std::fs::create_dir_all(project.join("src")).unwrap();
+        std::fs::write(
+            project.join("src").join("lib.rs"),
+            "mod db;\n\nfn run() {}\n",
+        )
+        .unwrap();
+        let scaffold = || {
+            ScaffoldDescription::new(
+                Opts::builder(template_dir.path())
+                    .project_name("project")
+                    .target_dir(&project)
+                    .append(true)
+                    .on_conflict(ConflictPolicy::Skip)
+                    .parameters(vec!["module=api"]),
+            )
+            .unwrap()
+            .scaffold_with_parameters(IndexMap::new())
+        };
+        let read = |file: &str| std::fs::read_to_string(project.join("src").join(file)).unwrap();
+
+        scaffold().unwrap();
+        scaffold().unwrap();
+        assert_eq!(read("lib.rs"), "mod db;\nmod api;\n\nfn run() {}\n");
+        assert_eq!(read("api.rs"), "// api\npub fn api() {}\n");
+
+        // the anchor must be found
+        std::fs::write(project.join("src").join("lib.rs"), "fn run() {}\n").unwrap();
+        let err = scaffold().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "cannot inject into \"src/lib.rs\" : no line matches `after` \"^mod \""
+        );
+
+        // files outside of the project are not edited
+        std::fs::write(
+            template_dir.path().join(".scaffold.toml"),
+            "[template]\n[[inject]]\nfile = \"{{module}}/lib.rs\"\ncontent = \"mod api;\"\n",
+        )
+        .unwrap();
+        std::fs::remove_dir_all(template_dir.path().join("src")).unwrap();
+        let err = ScaffoldDescription::new(
+            Opts::builder(template_dir.path())
+                .project_name("project")
+                .target_dir(&project)
+                .append(true)
+                .parameters(vec!["module=../other"]),
+        )
+        .unwrap()
+        .scaffold_with_parameters(IndexMap::new())
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "cannot edit \"../other/lib.rs\", it must be relative to the project directory, without `..`"
+        );
+    }
+
+    #[test]
+    fn patches_edit_manifests() {
+        let template_dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            template_dir.path().join(".scaffold.toml"),
+            r#"[template]
+
+[[patch]]
+file = "Cargo.toml"
+op = "append"
+key = "workspace.members"
+value = "{{name}}"
+
+[[patch]]
+file = "{{name}}/package.json"
+op = "set"
+key = "name"
+value = "@acme/{{name}}"
+"#,
+        )
+        .unwrap();
+        std::fs::create_dir(template_dir.path().join("{{name}}")).unwrap();
+        std::fs::write(
+            template_dir.path().join("{{name}}").join("package.json"),
+            "{\n  \"name\": \"\"\n}\n",
+        )
+        .unwrap();
+
+        let workspace = tempfile::tempdir().unwrap();
+        std::fs::write(
+            workspace.path().join("Cargo.toml"),
+            "[workspace]\nmembers = [\"core\"] # crates\n",
+        )
+        .unwrap();
+        ScaffoldDescription::new(
+            Opts::builder(template_dir.path())
+                .project_name("api")
+                .target_dir(workspace.path())
+                .append(true),
+        )
+        .unwrap()
+        .scaffold_with_parameters(IndexMap::new())
+        .unwrap();
+        let read = |file: &str| std::fs::read_to_string(workspace.path().join(file)).unwrap();
+        assert_eq!(
+            read("Cargo.toml"),
+            "[workspace]\nmembers = [\"core\", \"api\"] # crates\n"
+        );
+        assert_eq!(
+            read("api/package.json"),
+            "{\n  \"name\": \"@acme/api\"\n}\n"
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn crates_are_registered_in_the_workspace() {
+        // hooks are run from the project directory
+        let _cwd = CURRENT_DIR.lock().unwrap();
+        let template_dir = tempfile::tempdir().unwrap();
+        std::fs::write(template_dir.path().join(".scaffold.toml"), "[template]\n").unwrap();
+        std::fs::write(
+            template_dir.path().join("Cargo.toml"),
+            "[package]\nname = \"{{name}}\"\nversion = \"{{workspace_version}}\"\n",
+        )
+        .unwrap();
+
+        let repo = tempfile::tempdir().unwrap();
+        let manifest =
+            "[workspace]\nmembers = [\"core\"]\n\n[workspace.package]\nversion = \"0.3.0\"\n";
+        std::fs::write(repo.path().join("Cargo.toml"), manifest).unwrap();
+        let crates = repo.path().join("crates");
+        let opts = |name: &str| {
+            Opts::builder(template_dir.path())
+                .project_name(name)
+                .target_dir(crates.join(name))
+                .workspace(true)
+        };
+        let read = |file: &Path| std::fs::read_to_string(file).unwrap();
+
+        ScaffoldDescription::new(opts("api"))
+            .unwrap()
+            .scaffold_with_parameters(IndexMap::new())
+            .unwrap();
+        assert_eq!(
+            read(&crates.join("api").join("Cargo.toml")),
+            "[package]\nname = \"api\"\nversion = \"0.3.0\"\n"
+        );
+        assert_eq!(
+            read(&repo.path().join("Cargo.toml")),
+            manifest.replace("[\"core\"]", "[\"core\", \"crates/api\"]")
+        );
+
+        // the manifest is restored when the generation is rolled back
+        std::fs::write(
+            template_dir.path().join(".scaffold.toml"),
+            "[template]\n[hooks]\npost = [\"false\"]\n",
+        )
+        .unwrap();
+        assert!(ScaffoldDescription::new(opts("web").strict_hooks(true))
+            .unwrap()
+            .scaffold_with_parameters(IndexMap::new())
+            .is_err());
+        assert!(!crates.join("web").exists());
+        assert_eq!(
+            read(&repo.path().join("Cargo.toml")),
+            manifest.replace("[\"core\"]", "[\"core\", \"crates/api\"]")
+        );
+    }
+
+    #[test]
+    fn templates_inherit_their_parents() {
+        let templates = tempfile::tempdir().unwrap();
+        let write = |path: &str, content: &str| {
+            let path = templates.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        };
+        write(
+            "base/.scaffold.toml",
+            r#"[template]
+exclude = ["docs/**"]
+notes = "Licensed under {{license}}"
+
+[parameters.license]
+type = "string"
+message = "License"
+default = "MIT"
+
+[parameters.ci]
+type = "boolean"
+message = "Add a CI workflow ?"
+
+[hooks]
+pre = ["echo base"]
+"#,
+        );
+        write("base/LICENSE", "{{license}}\n");
+        write("base/README.md", "base\n");
+        write("base/docs/guide.md", "guide\n");
+        write("base/ci.yml", "name: {{name}}\n");
+        write(
+            "service/.scaffold.toml",
+            r#"[template]
+extends = "../base"
+exclude = ["ci.yml"]
+
+[parameters.ci]
+type = "boolean"
+message = "Add a GitHub workflow ?"
+
+[hooks]
+pre = ["echo service"]
+post = ["echo done"]
+"#,
+        );
+        write("service/README.md", "# {{name}}\n");
+
+        let target = tempfile::tempdir().unwrap();
+        let scaffold_desc = ScaffoldDescription::new(
+            Opts::builder(templates.path().join("service"))
+                .project_name("api")
+                .target_dir(target.path().join("api")),
+        )
+        .unwrap();
+        assert_eq!(
+            scaffold_desc.parameters.keys().collect::<Vec<_>>(),
+            ["license", "ci"]
+        );
+        assert_eq!(
+            scaffold_desc.parameters["ci"].message,
+            "Add a GitHub workflow ?"
+        );
+        let hooks = scaffold_desc.hooks.as_ref().unwrap();
+        assert_eq!(
+            hooks.pre,
+            Some(vec!["echo base".to_string(), "echo service".to_string()])
+        );
+        assert_eq!(hooks.post, Some(vec!["echo done".to_string()]));
+        assert_eq!(
+            scaffold_desc.template.notes.as_deref(),
+            Some("Licensed under {{license}}")
+        );
+
+        // without the hooks, which change the current directory
+        let scaffold_desc = ScaffoldDescription {
+            hooks: None,
+            ..scaffold_desc
+        };
+        let mut parameters = IndexMap::new();
+        parameters.insert(
+            "license".to_string(),
+            toml::Value::String("MIT".to_string()),
+        );
+        scaffold_desc.scaffold_with_parameters(parameters).unwrap();
+        let project = target.path().join("api");
+        let read = |file: &str| std::fs::read_to_string(project.join(file)).unwrap();
+        assert_eq!(read("README.md"), "# api\n");
+        assert_eq!(read("LICENSE"), "MIT\n");
+        assert!(!project.join("ci.yml").exists());
+        assert!(!project.join("docs").join("guide.md").exists());
+        assert!(!project.join(".scaffold.toml").exists());
+
+        write(
+            "base/.scaffold.toml",
+            "[template]\nextends = \"../service\"\n",
+        );
+        let err = ScaffoldDescription::new(
+            Opts::builder(templates.path().join("service")).project_name("api"),
+        )
+        .err()
+        .unwrap();
+        assert!(err
+            .to_string()
+            .ends_with("extends itself through \"../service\""));
+    }
+
+    #[test]
+    fn parents_are_fetched_from_the_repository_of_the_child() {
+        let origin = tempfile::tempdir().unwrap();
+        let repo_path = origin.path().join("templates.git");
+        let repo = git2::Repository::init(&repo_path).unwrap();
+        let url = format!("file://{}", repo_path.display());
+        let commit = |files: &[(&str, &str)]| {
+            let mut index = repo.index().unwrap();
+            for (path, content) in files {
+                let full_path = repo_path.join(path);
+                std::fs::create_dir_all(full_path.parent().unwrap()).unwrap();
+                std::fs::write(full_path, content).unwrap();
+                index.add_path(Path::new(path)).unwrap();
+            }
+            index.write().unwrap();
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let signature = git2::Signature::now("test", "test@example.com").unwrap();
+            let parents = repo.head().ok().map(|head| head.peel_to_commit().unwrap());
+            repo.commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                "templates",
+                &tree,
+                &parents.iter().collect::<Vec<_>>(),
+            )
+            .unwrap()
+        };
+        let v1 = commit(&[
+            ("base/.scaffold.toml", "[template]\n"),
+            ("base/LICENSE", "MIT\n"),
+            (
+                "service/.scaffold.toml",
+                "[template]\nextends = \"../base\"\n",
+            ),
+            ("service/README.md", "# {{name}}\n"),
+        ]);
+        repo.tag_lightweight("v1", &repo.find_object(v1, None).unwrap(), false)
+            .unwrap();
+        commit(&[
+            ("base/LICENSE", "Apache-2.0\n"),
+            (
+                "pinned/.scaffold.toml",
+                &format!(
+                    "[template]\nextends = {{ git = \"{url}\", ref = \"v1\", path = \"base\" }}\n"
+                ),
+            ),
+        ]);
+
+        let target = tempfile::tempdir().unwrap();
+        let scaffold = |template: &str, name: &str| {
+            ScaffoldDescription::new(
+                Opts::builder(&url)
+                    .repository_template_path(template)
+                    .project_name(name)
+                    .target_dir(target.path().join(name)),
+            )
+            .unwrap()
+            .scaffold_with_parameters(IndexMap::new())
+            .unwrap();
+            target.path().join(name)
+        };
+        let read = |path: std::path::PathBuf| std::fs::read_to_string(path).unwrap();
+
+        // only the directory of the child is checked out, the parent is checked out next to it
+        let service = scaffold("service", "api");
+        assert_eq!(read(service.join("README.md")), "# api\n");
+        assert_eq!(read(service.join("LICENSE")), "Apache-2.0\n");
+
+        // the parent is fetched at the given reference
+        let pinned = scaffold("pinned", "cli");
+        assert_eq!(read(pinned.join("LICENSE")), "MIT\n");
+    }
+
+    #[test]
+    fn partials_are_shared_between_files() {
+        let templates = tempfile::tempdir().unwrap();
+        let write = |path: &str, content: &str| {
+            let path = templates.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        };
+        write("base/.scaffold.toml", "[template]\n");
+        write(
+            "base/partials/license_header.hbs",
+            "// Copyright {{name}}\n",
+        );
+        write("base/partials/rust/imports.rs", "use std::fs;\n");
+        write(
+            "service/.scaffold.toml",
+            "[template]\nextends = \"../base\"\npartials = \"shared\"\n",
+        );
+        write(
+            "service/shared/license_header.hbs",
+            "// Licensed to {{name}}\n",
+        );
+        write(
+            "service/src/main.rs",
+            "{{> license_header}}{{> rust/imports}}\nfn main() {}\n",
+        );
+        write("service/src/lib.rs", "{{> license_header}}");
+        // the delimiters and disable_templating globs apply to the partials
+        write(
+            "base/.scaffold.toml",
+            r#"[template]
+disable_templating = ["partials/raw.txt"]
+
+[[template.delimiters]]
+glob = "partials/*.yml"
+open = "<%"
+close = "%>"
+"#,
+        );
+        write(
+            "base/partials/job.yml",
+            "name: <% name %>\nref: ${{ github.ref }}\n",
+        );
+        write("base/partials/raw.txt", "{{ untouched }}\n");
+        write("service/ci.yml", "{{> job}}{{> raw}}");
+
+        let target = tempfile::tempdir().unwrap();
+        let scaffold_desc = ScaffoldDescription::new(
+            Opts::builder(templates.path().join("service"))
+                .project_name("api")
+                .target_dir(target.path().join("api")),
+        )
+        .unwrap();
+        assert!(scaffold_desc.lint().unwrap().is_empty());
+        scaffold_desc
+            .scaffold_with_parameters(IndexMap::new())
+            .unwrap();
+        let project = target.path().join("api");
+        assert_eq!(
+            std::fs::read_to_string(project.join("src").join("main.rs")).unwrap(),
+            "// Licensed to api\nuse std::fs;\n\nfn main() {}\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(project.join("ci.yml")).unwrap(),
+            "name: api\nref: ${{ github.ref }}\n{{ untouched }}\n"
+        );
+        assert!(!project.join("shared").exists());
+        assert!(!project.join("partials").exists());
+    }
+
+    #[test]
+    fn script_helpers_are_registered() {
+        let template_dir = tempfile::tempdir().unwrap();
+        std::fs::write(template_dir.path().join(".scaffold.toml"), "[template]\n").unwrap();
+        std::fs::create_dir(template_dir.path().join("helpers")).unwrap();
+        std::fs::write(
+            template_dir.path().join("helpers").join("crate_ident.rhai"),
+            "let ident = params[0]; ident.replace(\"-\", \"_\"); ident",
+        )
+        .unwrap();
+        std::fs::write(
+            template_dir.path().join("main.rs"),
+            "use {{crate_ident name}};\n",
+        )
+        .unwrap();
+
+        let target = tempfile::tempdir().unwrap();
+        let scaffold_desc = ScaffoldDescription::new(
+            Opts::builder(template_dir.path())
+                .project_name("my-api")
+                .target_dir(target.path().join("my-api")),
+        )
+        .unwrap();
+        let generated = scaffold_desc.scaffold_with_parameters(IndexMap::new());
+        #[cfg(feature = "script_helpers")]
+        {
+            generated.unwrap();
+            let project = target.path().join("my-api");
+            assert_eq!(
+                std::fs::read_to_string(project.join("main.rs")).unwrap(),
+                "use my_api;\n"
+            );
+            assert!(!project.join("helpers").exists());
+
+            // the other files of the helpers directory are generated
+            std::fs::write(
+                template_dir.path().join("helpers").join("README.md"),
+                "# Helpers of {{name}}\n",
+            )
+            .unwrap();
+            ScaffoldDescription::new(
+                Opts::builder(template_dir.path())
+                    .project_name("cli")
+                    .target_dir(target.path().join("cli")),
+            )
+            .unwrap()
+            .scaffold_with_parameters(IndexMap::new())
+            .unwrap();
+            let helpers = target.path().join("cli").join("helpers");
+            assert_eq!(
+                std::fs::read_to_string(helpers.join("README.md")).unwrap(),
+                "# Helpers of cli\n"
+            );
+            assert!(!helpers.join("crate_ident.rhai").exists());
+        }
+        #[cfg(not(feature = "script_helpers"))]
+        assert!(generated
+            .unwrap_err()
+            .to_string()
+            .ends_with("cargo-scaffold is built without the script_helpers feature"));
+    }
+
+    #[test]
+    fn json_schema_is_up_to_date() {
+        // Regenerate it with `cargo scaffold schema > scaffold.schema.json`
+        assert_eq!(
+            ScaffoldDescription::json_schema(),
+            include_str!("../scaffold.schema.json")
+        );
+    }
+
     #[test]
     fn test_build_opts_works() {
         let opts = Opts::builder("/path/to/template");
@@ -870,11 +3349,24 @@ mod tests {
             Some(std::path::PathBuf::from("somepath"))
         );
 
+        // Test template_name can be set
+        assert!(opts.template_name.is_none());
+        let opts = opts.template_name("service");
+        assert_eq!(opts.template_name.as_deref(), Some("service"));
+
         // Test git_ref can be set
         assert!(opts.git_ref.is_none());
         let opts = opts.git_ref("main");
         assert_eq!(opts.git_ref, Some("main".to_string()));
 
+        // Test registry can be set
+        assert!(opts.registry.is_none());
+        let opts = opts.registry("sparse+https://my.registry/index/");
+        assert_eq!(
+            opts.registry,
+            Some("sparse+https://my.registry/index/".to_string())
+        );
+
         // Test target_dir
         assert!(opts.target_dir.is_none());
         let opts = opts.target_dir("target");
@@ -897,6 +3389,51 @@ mod tests {
             Some(std::path::PathBuf::from(".ssh/id_rsa"))
         );
 
+        // Test submodules can be set
+        assert!(!opts.submodules);
+        let opts = opts.submodules(true);
+        assert!(opts.submodules);
+
+        // Test strict can be set
+        assert!(!opts.strict);
+        let opts = opts.strict(true);
+        assert!(opts.strict);
+
+        // Test allow_unknown_keys can be set
+        assert!(!opts.allow_unknown_keys);
+        let opts = opts.allow_unknown_keys(true);
+        assert!(opts.allow_unknown_keys);
+
+        // Test strict_hooks can be set
+        assert!(!opts.strict_hooks);
+        let opts = opts.strict_hooks(true);
+        assert!(opts.strict_hooks);
+
+        // Test overwrite can be set
+        assert!(!opts.overwrite);
+        let opts = opts.overwrite(true);
+        assert!(opts.overwrite);
+
+        // Test on_conflict can be set
+        assert!(opts.on_conflict.is_none());
+        let opts = opts.on_conflict(ConflictPolicy::Rename);
+        assert_eq!(opts.on_conflict, Some(ConflictPolicy::Rename));
+
+        // Test warn_binary can be set
+        assert!(!opts.warn_binary);
+        let opts = opts.warn_binary(true);
+        assert!(opts.warn_binary);
+
+        // Test workspace can be set
+        assert!(!opts.workspace);
+        let opts = opts.workspace(true);
+        assert!(opts.workspace);
+
+        // Test umask can be set
+        assert!(opts.umask.is_none());
+        let opts = opts.umask(0o022);
+        assert_eq!(opts.umask, Some(0o022));
+
         // Test parameters can be set
         assert!(opts.parameters.is_empty());
         let opts = opts.parameters(vec!["key1=value1"]);
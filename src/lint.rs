@@ -0,0 +1,298 @@
+use handlebars::template::{
+    DecoratorTemplate, HelperTemplate, Parameter, Template, TemplateElement, TemplateMapping,
+};
+use indexmap::IndexSet;
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+/// Helpers changing the context of their block, relative paths inside them don't reference parameters
+const CONTEXT_HELPERS: &[&str] = &["each", "with", "forRange"];
+
+/// LintIssue: a problem found in a template file, a templated path, the notes or the hooks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LintIssue {
+    /// File containing the problem, relative to the template directory
+    pub path: PathBuf,
+    /// Line and column of the problem, starting at 1
+    pub position: Option<(usize, usize)>,
+    pub message: String,
+}
+
+impl fmt::Display for LintIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.position {
+            Some((line, column)) => write!(f, "{}:{line}:{column}: ", self.path.display())?,
+            None => write!(f, "{}: ", self.path.display())?,
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+struct Reference {
+    name: String,
+    path: PathBuf,
+    position: Option<(usize, usize)>,
+}
+
+/// Collect the variables referenced by templates to report the undeclared and unused parameters
+#[derive(Default)]
+pub(crate) struct Linter {
+    references: Vec<Reference>,
+    issues: Vec<LintIssue>,
+}
+
+impl Linter {
+    /// Parse the template and collect its references
+    pub(crate) fn check(&mut self, path: &Path, source: &str) {
+        match Template::compile(source) {
+            Ok(template) => self.template(path, &template, 0, None),
+            Err(e) => self.issues.push(LintIssue {
+                path: path.to_path_buf(),
+                position: e.pos(),
+                message: format!("invalid template: {}", e.reason()),
+            }),
+        }
+    }
+
+    /// Parse a template embedded in another file, like a file name or a hook, and collect its
+    /// references without positions as they would be relative to the embedded template
+    pub(crate) fn check_inline(&mut self, path: &Path, source: &str) {
+        let (references, issues) = (self.references.len(), self.issues.len());
+        self.check(path, source);
+        for reference in &mut self.references[references..] {
+            reference.position = None;
+        }
+        for issue in &mut self.issues[issues..] {
+            issue.position = None;
+        }
+    }
+
+    /// Report the references to undeclared parameters and the parameters never referenced
+    pub(crate) fn issues<'a>(
+        mut self,
+        declared: impl Iterator<Item = &'a str>,
+        parameters: impl Iterator<Item = &'a str>,
+    ) -> Vec<LintIssue> {
+        let declared = declared.collect::<IndexSet<_>>();
+        for reference in &self.references {
+            if !declared.contains(reference.name.as_str()) {
+                self.issues.push(LintIssue {
+                    path: reference.path.clone(),
+                    position: reference.position,
+                    message: format!("reference to undeclared parameter `{}`", reference.name),
+                });
+            }
+        }
+        for parameter in parameters {
+            if !self.references.iter().any(|r| r.name == parameter) {
+                self.issues.push(LintIssue {
+                    path: PathBuf::from(crate::SCAFFOLD_FILENAME),
+                    position: None,
+                    message: format!("parameter `{parameter}` is never used"),
+                });
+            }
+        }
+
+        self.issues
+    }
+
+    /// `depth` is the number of context changing blocks around the template
+    fn template(
+        &mut self,
+        path: &Path,
+        template: &Template,
+        depth: usize,
+        position: Option<(usize, usize)>,
+    ) {
+        for (idx, element) in template.elements.iter().enumerate() {
+            let position = template
+                .mapping
+                .get(idx)
+                .map(|TemplateMapping(line, column)| (*line, *column))
+                .or(position);
+            match element {
+                TemplateElement::Expression(helper)
+                | TemplateElement::HtmlExpression(helper)
+                | TemplateElement::HelperBlock(helper) => {
+                    self.helper(path, helper, depth, position)
+                }
+                TemplateElement::DecoratorExpression(decorator)
+                | TemplateElement::DecoratorBlock(decorator)
+                | TemplateElement::PartialExpression(decorator)
+                | TemplateElement::PartialBlock(decorator) => {
+                    self.decorator(path, decorator, depth, position)
+                }
+                TemplateElement::RawString(_) | TemplateElement::Comment(_) => {}
+            }
+        }
+    }
+
+    fn helper(
+        &mut self,
+        path: &Path,
+        helper: &HelperTemplate,
+        depth: usize,
+        position: Option<(usize, usize)>,
+    ) {
+        // `{{name}}` is a simple expression, the name of a helper otherwise
+        if helper.params.is_empty() && helper.hash.is_empty() && !helper.block {
+            self.parameter(path, &helper.name, depth, position);
+        }
+        for param in helper.params.iter().chain(helper.hash.values()) {
+            self.parameter(path, param, depth, position);
+        }
+
+        let changes_context = helper.block_param.is_some()
+            || matches!(&helper.name, Parameter::Name(name) if CONTEXT_HELPERS.contains(&name.as_str()));
+        let block_depth = if changes_context { depth + 1 } else { depth };
+        if let Some(template) = &helper.template {
+            self.template(path, template, block_depth, position);
+        }
+        if let Some(inverse) = &helper.inverse {
+            // the inverse block of `each` is rendered when there is nothing to iterate on
+            self.template(path, inverse, depth, position);
+        }
+    }
+
+    fn decorator(
+        &mut self,
+        path: &Path,
+        decorator: &DecoratorTemplate,
+        depth: usize,
+        position: Option<(usize, usize)>,
+    ) {
+        for param in decorator.params.iter().chain(decorator.hash.values()) {
+            self.parameter(path, param, depth, position);
+        }
+        if let Some(template) = &decorator.template {
+            self.template(path, template, depth, position);
+        }
+    }
+
+    fn parameter(
+        &mut self,
+        path: &Path,
+        parameter: &Parameter,
+        depth: usize,
+        position: Option<(usize, usize)>,
+    ) {
+        match parameter {
+            Parameter::Path(_) => {
+                let raw = parameter.as_name().unwrap_or_default();
+                if let Some(name) = root_variable(raw, depth) {
+                    self.references.push(Reference {
+                        name,
+                        path: path.to_path_buf(),
+                        position,
+                    });
+                }
+            }
+            Parameter::Subexpression(subexpression) => {
+                if let TemplateElement::Expression(helper) = &*subexpression.element {
+                    self.helper(path, helper, depth, position);
+                }
+            }
+            Parameter::Name(_) | Parameter::Literal(_) => {}
+        }
+    }
+}
+
+/// Name of the parameter referenced by the path, if it references the root context
+fn root_variable(raw: &str, depth: usize) -> Option<String> {
+    let rest = if let Some(rest) = raw.strip_prefix("@root") {
+        rest.trim_start_matches(['.', '/'])
+    } else if raw.starts_with('@') {
+        // local variables like @index
+        return None;
+    } else {
+        let mut rest = raw;
+        let mut up = 0;
+        while let Some(stripped) = rest.strip_prefix("../") {
+            rest = stripped;
+            up += 1;
+        }
+        if up != depth {
+            return None;
+        }
+        match rest.strip_prefix("this") {
+            Some("") => return None,
+            Some(stripped) if stripped.starts_with(['.', '/']) => &stripped[1..],
+            _ => rest,
+        }
+    };
+
+    let name = match rest.strip_prefix('[') {
+        Some(literal) => literal.split(']').next().unwrap_or_default(),
+        None => rest.split(['.', '/', '[']).next().unwrap_or_default(),
+    };
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn references(source: &str) -> Vec<String> {
+        let mut linter = Linter::default();
+        linter.check(Path::new("file"), source);
+        linter.references.into_iter().map(|r| r.name).collect()
+    }
+
+    #[test]
+    fn collect_references() {
+        assert_eq!(
+            references(
+                "{{name}} {{#if show_description}}{{description.text}}{{/if}} {{to_snake_case feature}}"
+            ),
+            vec!["name", "show_description", "description", "feature"]
+        );
+        assert_eq!(
+            references("{{@root.limit}} {{this.gender}} {{@index}} \\{{escaped}} {{{{raw}}}}{{raw_var}}{{{{/raw}}}}"),
+            vec!["limit", "gender"]
+        );
+        assert_eq!(
+            references(
+                "{{#each dependencies as |dependency|}}{{dependency}}{{../name}}{{version}}{{/each}}"
+            ),
+            vec!["dependencies", "name"]
+        );
+        assert_eq!(
+            references("{{#forRange limit}}{{@index}}{{../name}}{{/forRange}}"),
+            vec!["limit", "name"]
+        );
+        assert_eq!(
+            references("{{#if (eq gender \"other\")}}{{/if}}"),
+            vec!["gender"]
+        );
+    }
+
+    #[test]
+    fn report_issues() {
+        let mut linter = Linter::default();
+        linter.check(Path::new("README.md"), "# {{name}}\n\n{{descripton}}\n");
+        linter.check_inline(Path::new("src/{{feature}}.rs"), "{{feature}}.rs");
+        linter.check(Path::new("src/{{feature}}.rs"), "{{#if}");
+        let issues = linter
+            .issues(
+                ["name", "description", "feature"].into_iter(),
+                ["description", "feature"].into_iter(),
+            )
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+
+        assert_eq!(issues.len(), 3);
+        assert!(issues[0].starts_with("src/{{feature}}.rs:1:"));
+        assert!(issues[0].contains("invalid template"));
+        assert_eq!(
+            issues[1],
+            "README.md:3:1: reference to undeclared parameter `descripton`"
+        );
+        assert_eq!(
+            issues[2],
+            ".scaffold.toml: parameter `description` is never used"
+        );
+    }
+}
@@ -1,7 +1,8 @@
-use anyhow::Result;
-use clap::{Parser, Subcommand};
+use anyhow::{anyhow, Result};
+use clap::{Args, Parser, Subcommand};
+use console::{Emoji, Style};
 
-use cargo_scaffold::{Opts, ScaffoldDescription};
+use cargo_scaffold::{InitOpts, Opts, ScaffoldDescription, TemplateIndex, SCAFFOLD_FILENAME};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about=None)]
@@ -12,12 +13,131 @@ struct Cli {
 
 #[derive(Subcommand)]
 enum ScaffoldCommand {
-    Scaffold(Opts),
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+    Scaffold(ScaffoldArgs),
+}
+
+#[derive(Args)]
+struct ScaffoldArgs {
+    #[command(subcommand)]
+    command: Option<TemplateCommand>,
+
+    #[command(flatten)]
+    opts: Option<Opts>,
+}
+
+#[derive(Subcommand)]
+enum TemplateCommand {
+    /// List the templates of the template index
+    List(IndexOpts),
+    /// Search the templates of the template index by name, description or tag
+    Search {
+        /// Text to look for
+        query: String,
+        #[command(flatten)]
+        index: IndexOpts,
+    },
+    /// Check the .scaffold.toml of a template and report all the problems found
+    Validate(Opts),
+    /// Report the references to undeclared parameters and the parameters never used by a template
+    Lint(Opts),
+    /// Turn an existing project into a template
+    Init(InitOpts),
+    /// Print the JSON Schema of the .scaffold.toml file
+    Schema,
+    /// Scaffold a project from a template of the template index, given its name
+    New {
+        #[command(flatten)]
+        index: IndexOpts,
+        #[command(flatten)]
+        opts: Opts,
+    },
+}
+
+#[derive(Args)]
+struct IndexOpts {
+    /// Template index location: a TOML file, a directory or a git repository
+    /// (defaults to scaffold-index.toml in the cargo home directory)
+    #[arg(long = "index", env = "CARGO_SCAFFOLD_INDEX")]
+    index: Option<String>,
 }
 
 fn main() -> Result<()> {
     let opts = Cli::parse();
     match opts.command {
-        ScaffoldCommand::Scaffold(opts) => ScaffoldDescription::new(opts)?.scaffold(),
+        ScaffoldCommand::Scaffold(ScaffoldArgs {
+            command: None,
+            opts: Some(opts),
+        }) => ScaffoldDescription::new(opts)?.scaffold(),
+        ScaffoldCommand::Scaffold(ScaffoldArgs {
+            command: None,
+            opts: None,
+        }) => unreachable!("the template is required without subcommand"),
+        ScaffoldCommand::Scaffold(ScaffoldArgs {
+            command: Some(command),
+            ..
+        }) => match command {
+            TemplateCommand::List(IndexOpts { index }) => {
+                let index = TemplateIndex::load(index.as_deref())?;
+                TemplateIndex::print(&index.entries().iter().collect::<Vec<_>>());
+                Ok(())
+            }
+            TemplateCommand::Search {
+                query,
+                index: IndexOpts { index },
+            } => {
+                let index = TemplateIndex::load(index.as_deref())?;
+                TemplateIndex::print(&index.search(&query));
+                Ok(())
+            }
+            TemplateCommand::Validate(opts) => {
+                let diagnostics = ScaffoldDescription::validate(opts)?;
+                if diagnostics.is_empty() {
+                    println!(
+                        "{} {}",
+                        Emoji("✅", ""),
+                        Style::new().green().apply_to("The template is valid")
+                    );
+                    return Ok(());
+                }
+                let red = Style::new().red();
+                for diagnostic in &diagnostics {
+                    println!(
+                        "{} {}",
+                        Emoji("❌", ""),
+                        red.apply_to(format!("{SCAFFOLD_FILENAME}:{diagnostic}"))
+                    );
+                }
+                Err(anyhow!("{} problem(s) found", diagnostics.len()))
+            }
+            TemplateCommand::Lint(opts) => {
+                let issues = ScaffoldDescription::new(opts)?.lint()?;
+                if issues.is_empty() {
+                    println!(
+                        "{} {}",
+                        Emoji("✅", ""),
+                        Style::new().green().apply_to("No problem found")
+                    );
+                    return Ok(());
+                }
+                let yellow = Style::new().yellow();
+                for issue in &issues {
+                    println!("{} {}", Emoji("⚠️", ""), yellow.apply_to(issue));
+                }
+                Err(anyhow!("{} problem(s) found", issues.len()))
+            }
+            TemplateCommand::Init(opts) => cargo_scaffold::init(opts),
+            TemplateCommand::Schema => {
+                print!("{}", ScaffoldDescription::json_schema());
+                Ok(())
+            }
+            TemplateCommand::New {
+                index: IndexOpts { index },
+                opts,
+            } => {
+                let opts = TemplateIndex::load(index.as_deref())?.resolve(opts)?;
+                ScaffoldDescription::new(opts)?.scaffold()
+            }
+        },
     }
 }
@@ -0,0 +1,770 @@
+use anyhow::{anyhow, Result};
+use handlebars::Handlebars;
+use indexmap::IndexMap;
+use std::ops::Range;
+use std::path::Path;
+use toml_edit::{DocumentMut, Item, Key, TableLike};
+
+use crate::{PatchOperation, Value};
+
+/// Format of a patched file, given its extension
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Format {
+    Toml,
+    Json,
+}
+
+impl Format {
+    pub(crate) fn of(file: &Path) -> Option<Self> {
+        match file.extension()?.to_str()? {
+            "toml" => Some(Self::Toml),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Dotted key (i.e.: `workspace.members` or `dependencies."serde.json"`) split in its parts
+pub(crate) fn parse_key(key: &str) -> Result<Vec<Key>> {
+    Key::parse(key).map_err(|_| anyhow!("invalid key {key:?}"))
+}
+
+/// Render the strings of the value, and the keys of its tables
+pub(crate) fn render_value(
+    template_engine: &Handlebars,
+    value: &Value,
+    parameters: &IndexMap<String, Value>,
+) -> Result<Value> {
+    let render = |s: &str| {
+        template_engine
+            .render_template(s, parameters)
+            .map_err(|e| anyhow!("cannot render patch value : {}", e))
+    };
+    Ok(match value {
+        Value::String(s) => Value::String(render(s)?),
+        Value::Array(values) => Value::Array(
+            values
+                .iter()
+                .map(|v| render_value(template_engine, v, parameters))
+                .collect::<Result<_>>()?,
+        ),
+        Value::Table(table) => Value::Table(
+            table
+                .iter()
+                .map(|(k, v)| Ok((render(k)?, render_value(template_engine, v, parameters)?)))
+                .collect::<Result<_>>()?,
+        ),
+        value => value.clone(),
+    })
+}
+
+/// Apply the operation to the content of the file, keeping its formatting: only the edited values
+/// of JSON files are written again, indented like the rest of the file.
+/// Returns None when the content is unchanged.
+pub(crate) fn patch(
+    format: Format,
+    content: &str,
+    op: PatchOperation,
+    key: &[Key],
+    value: &Value,
+) -> Result<Option<String>> {
+    if op == PatchOperation::Merge && !value.is_table() {
+        return Err(anyhow!("the value to merge must be a table"));
+    }
+    let patched = match format {
+        Format::Toml => {
+            let mut document = content
+                .parse::<DocumentMut>()
+                .map_err(|e| anyhow!("invalid TOML: {}", e.message().trim()))?;
+            patch_toml(document.as_table_mut(), op, key, value)?;
+            document.to_string()
+        }
+        Format::Json => {
+            let mut document: serde_json::Value =
+                serde_json::from_str(content).map_err(|e| anyhow!("invalid JSON: {}", e))?;
+            let original = document.clone();
+            let key = key.iter().map(Key::get).collect::<Vec<_>>();
+            patch_json(&mut document, op, &key, to_json(value))?;
+            if document == original {
+                return Ok(None);
+            }
+            edit_json(content, &original, &document)?
+        }
+    };
+
+    Ok((patched != content).then_some(patched))
+}
+
+fn patch_toml(
+    mut table: &mut dyn TableLike,
+    op: PatchOperation,
+    key: &[Key],
+    value: &Value,
+) -> Result<()> {
+    let (last, parents) = key
+        .split_last()
+        .ok_or_else(|| anyhow!("the key is empty"))?;
+    let mut at_root = true;
+    for parent in parents {
+        let item = table.entry_format(parent).or_insert_with(|| {
+            let mut new_table = toml_edit::Table::new();
+            new_table.set_implicit(true);
+            Item::Table(new_table)
+        });
+        table = item
+            .as_table_like_mut()
+            .ok_or_else(|| anyhow!("`{}` is not a table", parent.get()))?;
+        at_root = false;
+    }
+
+    match op {
+        PatchOperation::Set => {
+            table.insert(last.get(), to_toml_item(value, at_root));
+        }
+        PatchOperation::Append => {
+            let item = table
+                .entry_format(last)
+                .or_insert_with(|| toml_edit::value(toml_edit::Array::new()));
+            let array = item
+                .as_array_mut()
+                .ok_or_else(|| anyhow!("`{}` is not an array", last.get()))?;
+            let values = match value {
+                Value::Array(values) => values.as_slice(),
+                value => std::slice::from_ref(value),
+            };
+            for value in values {
+                let mut value = to_toml_value(value);
+                if array.iter().any(|v| same_toml_value(v, &value)) {
+                    continue;
+                }
+                // New elements are laid out like the last one (i.e.: one per line),
+                // unless it is the first one which has no leading space
+                let laid_out = array.iter().last().filter(|last| {
+                    array.len() > 1
+                        || last
+                            .decor()
+                            .prefix()
+                            .and_then(|prefix| prefix.as_str())
+                            .is_some_and(|prefix| prefix.contains('\n'))
+                });
+                match laid_out {
+                    Some(last) => {
+                        *value.decor_mut() = last.decor().clone();
+                        array.push_formatted(value);
+                    }
+                    None => array.push(value),
+                }
+            }
+        }
+        PatchOperation::Merge => {
+            let item = table.entry_format(last).or_insert_with(|| match at_root {
+                true => Item::Table(toml_edit::Table::new()),
+                false => toml_edit::value(toml_edit::InlineTable::new()),
+            });
+            let table = item
+                .as_table_like_mut()
+                .ok_or_else(|| anyhow!("`{}` is not a table", last.get()))?;
+            merge_toml(table, value);
+        }
+    }
+
+    Ok(())
+}
+
+fn merge_toml(table: &mut dyn TableLike, value: &Value) {
+    let Value::Table(values) = value else {
+        return;
+    };
+    for (key, value) in values {
+        match (table.get_mut(key).and_then(Item::as_table_like_mut), value) {
+            (Some(existing), Value::Table(_)) => merge_toml(existing, value),
+            _ => {
+                table.insert(key, toml_edit::value(to_toml_value(value)));
+            }
+        }
+    }
+}
+
+/// Tables set in the document root are standard tables (i.e.: `[workspace]`), inline ones elsewhere
+fn to_toml_item(value: &Value, at_root: bool) -> Item {
+    match value {
+        Value::Table(values) if at_root => {
+            let mut table = toml_edit::Table::new();
+            for (key, value) in values {
+                table.insert(key, toml_edit::value(to_toml_value(value)));
+            }
+            Item::Table(table)
+        }
+        value => toml_edit::value(to_toml_value(value)),
+    }
+}
+
+fn to_toml_value(value: &Value) -> toml_edit::Value {
+    match value {
+        Value::String(s) => s.into(),
+        Value::Integer(i) => (*i).into(),
+        Value::Float(f) => (*f).into(),
+        Value::Boolean(b) => (*b).into(),
+        Value::Datetime(datetime) => (*datetime).into(),
+        Value::Array(values) => values
+            .iter()
+            .map(to_toml_value)
+            .collect::<toml_edit::Array>()
+            .into(),
+        Value::Table(values) => values
+            .iter()
+            .map(|(key, value)| (key.clone(), to_toml_value(value)))
+            .collect::<toml_edit::InlineTable>()
+            .into(),
+    }
+}
+
+fn same_toml_value(a: &toml_edit::Value, b: &toml_edit::Value) -> bool {
+    let normalized = |value: &toml_edit::Value| {
+        let mut value = value.clone();
+        value.decor_mut().clear();
+        match &mut value {
+            toml_edit::Value::Array(array) => array.fmt(),
+            toml_edit::Value::InlineTable(table) => table.fmt(),
+            _ => {}
+        }
+        value.to_string()
+    };
+    normalized(a) == normalized(b)
+}
+
+fn patch_json(
+    mut document: &mut serde_json::Value,
+    op: PatchOperation,
+    key: &[&str],
+    value: serde_json::Value,
+) -> Result<()> {
+    let (last, parents) = key
+        .split_last()
+        .ok_or_else(|| anyhow!("the key is empty"))?;
+    for parent in parents {
+        document = document
+            .as_object_mut()
+            .ok_or_else(|| anyhow!("the parent of `{parent}` is not an object"))?
+            .entry(*parent)
+            .or_insert_with(|| serde_json::json!({}));
+    }
+    let object = document
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("the parent of `{last}` is not an object"))?;
+
+    match op {
+        PatchOperation::Set => {
+            object.insert(last.to_string(), value);
+        }
+        PatchOperation::Append => {
+            let array = object
+                .entry(*last)
+                .or_insert_with(|| serde_json::json!([]))
+                .as_array_mut()
+                .ok_or_else(|| anyhow!("`{last}` is not an array"))?;
+            let values = match value {
+                serde_json::Value::Array(values) => values,
+                value => vec![value],
+            };
+            for value in values {
+                if !array.contains(&value) {
+                    array.push(value);
+                }
+            }
+        }
+        PatchOperation::Merge => {
+            let existing = object.entry(*last).or_insert_with(|| serde_json::json!({}));
+            if !existing.is_object() {
+                return Err(anyhow!("`{last}` is not an object"));
+            }
+            merge_json(existing, value);
+        }
+    }
+
+    Ok(())
+}
+
+fn merge_json(existing: &mut serde_json::Value, value: serde_json::Value) {
+    match (existing, value) {
+        (serde_json::Value::Object(existing), serde_json::Value::Object(values)) => {
+            for (key, value) in values {
+                match existing.get_mut(&key) {
+                    Some(existing) if existing.is_object() && value.is_object() => {
+                        merge_json(existing, value)
+                    }
+                    _ => {
+                        existing.insert(key, value);
+                    }
+                }
+            }
+        }
+        (existing, value) => *existing = value,
+    }
+}
+
+fn to_json(value: &Value) -> serde_json::Value {
+    match value {
+        Value::String(s) => s.clone().into(),
+        Value::Integer(i) => (*i).into(),
+        Value::Float(f) => (*f).into(),
+        Value::Boolean(b) => (*b).into(),
+        Value::Datetime(datetime) => datetime.to_string().into(),
+        Value::Array(values) => values.iter().map(to_json).collect(),
+        Value::Table(values) => values
+            .iter()
+            .map(|(key, value)| (key.clone(), to_json(value)))
+            .collect::<serde_json::Map<_, _>>()
+            .into(),
+    }
+}
+
+/// Position of a JSON value in the content, with its members or elements
+struct JsonSpan {
+    range: Range<usize>,
+    children: JsonChildren,
+}
+
+enum JsonChildren {
+    /// Key, start of the key and value of each member
+    Object(Vec<(String, usize, JsonSpan)>),
+    Array(Vec<JsonSpan>),
+    Scalar,
+}
+
+impl JsonSpan {
+    /// Start of the first member or element, and of the last one with its end
+    fn children_bounds(&self) -> Option<(usize, usize, usize)> {
+        match &self.children {
+            JsonChildren::Object(members) => {
+                let (_, first, _) = members.first()?;
+                let (_, last, value) = members.last()?;
+                Some((*first, *last, value.range.end))
+            }
+            JsonChildren::Array(elements) => Some((
+                elements.first()?.range.start,
+                elements.last()?.range.start,
+                elements.last()?.range.end,
+            )),
+            JsonChildren::Scalar => None,
+        }
+    }
+}
+
+/// Parser of the spans of a content already known to be valid JSON
+struct JsonSpans<'a> {
+    content: &'a str,
+    pos: usize,
+}
+
+impl JsonSpans<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.content.as_bytes().get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|c| c.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn value(&mut self) -> Result<JsonSpan> {
+        self.skip_whitespace();
+        let start = self.pos;
+        let children = match self.peek() {
+            Some(b'{') => {
+                self.pos += 1;
+                let mut members = Vec::new();
+                loop {
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(b'}') => break,
+                        Some(b',') => self.pos += 1,
+                        Some(b'"') => {
+                            let key_start = self.pos;
+                            self.string()?;
+                            let key = serde_json::from_str(&self.content[key_start..self.pos])?;
+                            self.skip_whitespace();
+                            if self.peek() != Some(b':') {
+                                return Err(anyhow!("invalid JSON at byte {}", self.pos));
+                            }
+                            self.pos += 1;
+                            members.push((key, key_start, self.value()?));
+                        }
+                        _ => return Err(anyhow!("invalid JSON at byte {}", self.pos)),
+                    }
+                }
+                self.pos += 1;
+                JsonChildren::Object(members)
+            }
+            Some(b'[') => {
+                self.pos += 1;
+                let mut elements = Vec::new();
+                loop {
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(b']') => break,
+                        Some(b',') => self.pos += 1,
+                        Some(_) => elements.push(self.value()?),
+                        None => return Err(anyhow!("invalid JSON at byte {}", self.pos)),
+                    }
+                }
+                self.pos += 1;
+                JsonChildren::Array(elements)
+            }
+            Some(b'"') => {
+                self.string()?;
+                JsonChildren::Scalar
+            }
+            Some(_) => {
+                while self
+                    .peek()
+                    .is_some_and(|c| !matches!(c, b',' | b']' | b'}') && !c.is_ascii_whitespace())
+                {
+                    self.pos += 1;
+                }
+                JsonChildren::Scalar
+            }
+            None => return Err(anyhow!("invalid JSON at byte {}", self.pos)),
+        };
+
+        Ok(JsonSpan {
+            range: start..self.pos,
+            children,
+        })
+    }
+
+    fn string(&mut self) -> Result<()> {
+        self.pos += 1;
+        loop {
+            match self.peek() {
+                Some(b'\\') => self.pos += 2,
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                Some(_) => self.pos += 1,
+                None => return Err(anyhow!("invalid JSON at byte {}", self.pos)),
+            }
+        }
+    }
+}
+
+/// Write the differences between the original and the patched document in the content, leaving
+/// the rest of it as is. Patches only add or replace values, and new members come last.
+fn edit_json(
+    content: &str,
+    original: &serde_json::Value,
+    patched: &serde_json::Value,
+) -> Result<String> {
+    let span = JsonSpans { content, pos: 0 }.value()?;
+    let indent = content
+        .lines()
+        .skip(1)
+        .map(|line| &line[..line.len() - line.trim_start().len()])
+        .find(|indent| !indent.is_empty())
+        .unwrap_or("  ");
+    let mut edits = Vec::new();
+    json_edits(content, indent, &span, original, patched, &mut edits)?;
+    // Edits don't overlap, apply them from the end to keep the positions of the others
+    edits.sort_by_key(|(range, _)| std::cmp::Reverse(range.start));
+    let mut content = content.to_string();
+    for (range, text) in edits {
+        content.replace_range(range, &text);
+    }
+
+    Ok(content)
+}
+
+fn json_edits(
+    content: &str,
+    indent: &str,
+    span: &JsonSpan,
+    original: &serde_json::Value,
+    patched: &serde_json::Value,
+    edits: &mut Vec<(Range<usize>, String)>,
+) -> Result<()> {
+    if original == patched {
+        return Ok(());
+    }
+    // New members and elements of a non empty object or array, the rest is written again
+    let mut added = Vec::new();
+    match (&span.children, original, patched) {
+        (
+            JsonChildren::Object(members),
+            serde_json::Value::Object(original),
+            serde_json::Value::Object(patched),
+        ) if !members.is_empty() => {
+            for (key, value) in patched {
+                let existing = members.iter().rev().find(|(k, ..)| k == key);
+                match (existing, original.get(key)) {
+                    (Some((.., span)), Some(original)) => {
+                        json_edits(content, indent, span, original, value, edits)?
+                    }
+                    _ => added.push((Some(key), value)),
+                }
+            }
+        }
+        (
+            JsonChildren::Array(elements),
+            serde_json::Value::Array(original),
+            serde_json::Value::Array(patched),
+        ) if !elements.is_empty() && patched.starts_with(original) => {
+            added.extend(patched[original.len()..].iter().map(|value| (None, value)));
+        }
+        _ => {
+            let base = line_indent(content, span.range.start);
+            edits.push((span.range.clone(), to_json_string(patched, indent, base)?));
+            return Ok(());
+        }
+    }
+    let Some((first, last, end)) = span.children_bounds() else {
+        return Ok(());
+    };
+    if added.is_empty() {
+        return Ok(());
+    }
+
+    // Laid out like the existing ones, one per line or all on the same line
+    let multiline = content[span.range.start..first].contains('\n');
+    let base = line_indent(content, last);
+    let mut text = String::new();
+    for (key, value) in added {
+        let value = match multiline {
+            true => {
+                text.push_str(",\n");
+                text.push_str(base);
+                to_json_string(value, indent, base)?
+            }
+            false => {
+                text.push_str(", ");
+                serde_json::to_string(value)?
+            }
+        };
+        if let Some(key) = key {
+            text.push_str(&serde_json::to_string(key)?);
+            text.push_str(": ");
+        }
+        text.push_str(&value);
+    }
+    edits.push((end..end, text));
+
+    Ok(())
+}
+
+/// Indentation of the line containing the position
+fn line_indent(content: &str, pos: usize) -> &str {
+    let line = &content[content[..pos].rfind('\n').map_or(0, |i| i + 1)..];
+    &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+}
+
+/// Pretty JSON indented with `indent`, its lines after the first one starting with `base`
+fn to_json_string(value: &serde_json::Value, indent: &str, base: &str) -> Result<String> {
+    use serde::Serialize;
+
+    let mut json = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+    value.serialize(&mut serde_json::Serializer::with_formatter(
+        &mut json, formatter,
+    ))?;
+
+    Ok(String::from_utf8(json)?.replace('\n', &format!("\n{base}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(file: &str, content: &str, op: PatchOperation, key: &str, value: &str) -> String {
+        let value: Value =
+            toml::from_str::<toml::Table>(&format!("value = {value}")).unwrap()["value"].clone();
+        patch(
+            Format::of(Path::new(file)).unwrap(),
+            content,
+            op,
+            &parse_key(key).unwrap(),
+            &value,
+        )
+        .unwrap()
+        .unwrap_or_else(|| content.to_string())
+    }
+
+    const CARGO_TOML: &str = r#"[workspace]
+# Crates of the workspace
+members = [
+    "core",
+    "cli",
+]
+
+[workspace.dependencies]
+serde = "1.0" # serialization
+"#;
+
+    #[test]
+    fn toml_keeps_formatting() {
+        assert_eq!(
+            apply(
+                "Cargo.toml",
+                CARGO_TOML,
+                PatchOperation::Append,
+                "workspace.members",
+                r#"["api", "cli"]"#
+            ),
+            r#"[workspace]
+# Crates of the workspace
+members = [
+    "core",
+    "cli",
+    "api",
+]
+
+[workspace.dependencies]
+serde = "1.0" # serialization
+"#
+        );
+        assert_eq!(
+            apply(
+                "Cargo.toml",
+                CARGO_TOML,
+                PatchOperation::Merge,
+                "workspace.dependencies",
+                r#"{ tokio = { version = "1", features = ["full"] } }"#
+            ),
+            format!("{CARGO_TOML}tokio = {{ version = \"1\", features = [\"full\"] }}\n")
+        );
+        assert_eq!(
+            apply(
+                "Cargo.toml",
+                "[package]\nname = \"api\"\n",
+                PatchOperation::Set,
+                "package.metadata.\"docs.rs\".all-features",
+                "true"
+            ),
+            "[package]\nname = \"api\"\n\n[package.metadata.\"docs.rs\"]\nall-features = true\n"
+        );
+        assert_eq!(
+            apply(
+                "Cargo.toml",
+                "[package]\nname = \"api\"\n",
+                PatchOperation::Merge,
+                "dependencies",
+                r#"{ serde = "1.0" }"#
+            ),
+            "[package]\nname = \"api\"\n\n[dependencies]\nserde = \"1.0\"\n"
+        );
+    }
+
+    #[test]
+    fn json_keeps_formatting() {
+        let package = "{\n    \"name\": \"web\",\n    \"scripts\": {\n        \"build\": \"vite build\"\n    }\n}\n";
+        assert_eq!(
+            apply(
+                "package.json",
+                package,
+                PatchOperation::Merge,
+                "scripts",
+                r#"{ test = "vitest" }"#
+            ),
+            "{\n    \"name\": \"web\",\n    \"scripts\": {\n        \"build\": \"vite build\",\n        \"test\": \"vitest\"\n    }\n}\n"
+        );
+
+        let package = r#"{
+  "name": "web",
+  "files": ["dist", "src"],
+  "scripts": {"build": "vite build"},
+  "engines": {},
+  "version": 1.0e0
+}
+"#;
+        let package = apply(
+            "package.json",
+            package,
+            PatchOperation::Append,
+            "files",
+            r#"["src", "types"]"#,
+        );
+        let package = apply(
+            "package.json",
+            &package,
+            PatchOperation::Merge,
+            "scripts",
+            r#"{ test = "vitest" }"#,
+        );
+        let package = apply(
+            "package.json",
+            &package,
+            PatchOperation::Set,
+            "engines.node",
+            r#"">=20""#,
+        );
+        let package = apply(
+            "package.json",
+            &package,
+            PatchOperation::Set,
+            "publishConfig.access",
+            r#""public""#,
+        );
+        assert_eq!(
+            package,
+            r#"{
+  "name": "web",
+  "files": ["dist", "src", "types"],
+  "scripts": {"build": "vite build", "test": "vitest"},
+  "engines": {
+    "node": ">=20"
+  },
+  "version": 1.0e0,
+  "publishConfig": {
+    "access": "public"
+  }
+}
+"#
+        );
+        assert_eq!(
+            apply(
+                "tsconfig.json",
+                "{\n\t\"compilerOptions\": {\n\t\t\"strict\": false\n\t}\n}",
+                PatchOperation::Set,
+                "compilerOptions.strict",
+                "true"
+            ),
+            "{\n\t\"compilerOptions\": {\n\t\t\"strict\": true\n\t}\n}"
+        );
+    }
+
+    #[test]
+    fn unchanged_and_errors() {
+        let value = Value::String("cli".to_string());
+        let members = parse_key("workspace.members").unwrap();
+        assert_eq!(
+            patch(
+                Format::Toml,
+                CARGO_TOML,
+                PatchOperation::Append,
+                &members,
+                &value
+            )
+            .unwrap(),
+            None
+        );
+        assert_eq!(
+            patch(
+                Format::Toml,
+                CARGO_TOML,
+                PatchOperation::Append,
+                &parse_key("workspace").unwrap(),
+                &value
+            )
+            .unwrap_err()
+            .to_string(),
+            "`workspace` is not an array"
+        );
+        assert_eq!(
+            patch(Format::Json, "{}", PatchOperation::Merge, &members, &value)
+                .unwrap_err()
+                .to_string(),
+            "the value to merge must be a table"
+        );
+        assert_eq!(Format::of(Path::new("ci.yml")), None);
+    }
+}
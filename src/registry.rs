@@ -0,0 +1,348 @@
+use anyhow::{anyhow, Context, Result};
+use console::{Emoji, Style};
+use flate2::read::GzDecoder;
+use semver::{Version, VersionReq};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub(crate) const CRATE_PREFIX: &str = "crate:";
+const CRATES_IO_INDEX: &str = "sparse+https://index.crates.io/";
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct CrateSpec {
+    name: String,
+    version_req: VersionReq,
+}
+
+#[derive(Debug, PartialEq)]
+enum Registry {
+    /// A sparse HTTP index, as served by crates.io or any alternate registry
+    Sparse(String),
+    /// A local registry directory, laid out like `cargo local-registry` does (`index/` + `.crate` files)
+    Local(PathBuf),
+}
+
+#[derive(Deserialize)]
+struct IndexEntry {
+    vers: String,
+    cksum: String,
+    #[serde(default)]
+    yanked: bool,
+}
+
+#[derive(Deserialize)]
+struct RegistryConfig {
+    dl: String,
+}
+
+impl CrateSpec {
+    /// Parse a spec like `my-template@1.4` (the version requirement is optional)
+    pub(crate) fn parse(spec: &str) -> Result<Self> {
+        let (name, version_req) = match spec.split_once('@') {
+            Some((name, req)) => (name, req),
+            None => (spec, "*"),
+        };
+        if name.is_empty() {
+            return Err(anyhow!("missing crate name in template source {spec:?}"));
+        }
+        // Also keeps the name from escaping the directory of a local index
+        if !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(anyhow!(
+                "invalid crate name {name:?} in template source {spec:?}, only ASCII letters, digits, `-` and `_` are allowed"
+            ));
+        }
+        let version_req = VersionReq::parse(version_req)
+            .with_context(|| format!("invalid version requirement in template source {spec:?}"))?;
+
+        Ok(Self {
+            name: name.to_string(),
+            version_req,
+        })
+    }
+}
+
+impl Registry {
+    fn from_location(location: Option<&str>) -> Self {
+        let location = location.unwrap_or(CRATES_IO_INDEX);
+        if let Some(url) = location.strip_prefix("sparse+") {
+            Registry::Sparse(with_trailing_slash(url))
+        } else if location.starts_with("http://") || location.starts_with("https://") {
+            Registry::Sparse(with_trailing_slash(location))
+        } else {
+            let path = location.strip_prefix("file://").unwrap_or(location);
+            Registry::Local(PathBuf::from(path))
+        }
+    }
+
+    fn index_file(&self, name: &str) -> Result<String> {
+        let path = index_path(name);
+        match self {
+            Registry::Sparse(url) => http_get_string(&format!("{url}{path}"))
+                .with_context(|| format!("cannot find crate {name:?} in registry {url}")),
+            Registry::Local(dir) => fs::read_to_string(dir.join("index").join(&path))
+                .with_context(|| format!("cannot find crate {name:?} in registry {dir:?}")),
+        }
+    }
+
+    fn download(&self, name: &str, entry: &IndexEntry) -> Result<Vec<u8>> {
+        let version = &entry.vers;
+        match self {
+            Registry::Sparse(url) => {
+                let config: RegistryConfig =
+                    serde_json::from_str(&http_get_string(&format!("{url}config.json"))?)
+                        .with_context(|| format!("invalid config.json in registry {url}"))?;
+                http_get_bytes(&download_url(&config.dl, name, version, &entry.cksum))
+            }
+            Registry::Local(dir) => {
+                let path = dir.join(format!("{name}-{version}.crate"));
+                fs::read(&path).with_context(|| format!("cannot read {path:?}"))
+            }
+        }
+    }
+}
+
+/// Resolve the crate described by `spec` in the registry, download it and unpack it in `target_dir`.
+///
+/// Returns the path of the unpacked crate, which is used as the template directory.
+pub(crate) fn fetch(spec: &str, registry: Option<&str>, target_dir: &Path) -> Result<PathBuf> {
+    let spec = CrateSpec::parse(spec)?;
+    let registry = Registry::from_location(registry);
+
+    let cyan = Style::new().cyan();
+    println!(
+        "{} {}",
+        Emoji("🔄", ""),
+        cyan.apply_to(format!("Resolving crate {}…", spec.name)),
+    );
+
+    let entry = resolve(&spec, &registry.index_file(&spec.name)?)?;
+    println!(
+        "{} {}",
+        Emoji("📦", ""),
+        cyan.apply_to(format!("Downloading {} v{}…", spec.name, entry.vers)),
+    );
+
+    let archive = registry.download(&spec.name, &entry)?;
+    let checksum = format!("{:x}", Sha256::digest(&archive));
+    if checksum != entry.cksum {
+        return Err(anyhow!(
+            "checksum mismatch for {} v{}: expected {}, got {}",
+            spec.name,
+            entry.vers,
+            entry.cksum,
+            checksum
+        ));
+    }
+
+    tar::Archive::new(GzDecoder::new(archive.as_slice()))
+        .unpack(target_dir)
+        .with_context(|| format!("cannot unpack {} v{}", spec.name, entry.vers))?;
+
+    Ok(target_dir.join(format!("{}-{}", spec.name, entry.vers)))
+}
+
+/// Pick the highest non-yanked version matching the requirement from the index file content
+fn resolve(spec: &CrateSpec, index_file: &str) -> Result<IndexEntry> {
+    let mut best: Option<(Version, IndexEntry)> = None;
+    for line in index_file.lines().filter(|l| !l.trim().is_empty()) {
+        let entry: IndexEntry = serde_json::from_str(line)
+            .with_context(|| format!("invalid index entry for crate {:?}", spec.name))?;
+        let Ok(version) = Version::parse(&entry.vers) else {
+            continue;
+        };
+        if entry.yanked || !spec.version_req.matches(&version) {
+            continue;
+        }
+        if best.as_ref().is_none_or(|(v, _)| version > *v) {
+            best = Some((version, entry));
+        }
+    }
+
+    best.map(|(_, entry)| entry).ok_or_else(|| {
+        anyhow!(
+            "no version of crate {:?} matches {}",
+            spec.name,
+            spec.version_req
+        )
+    })
+}
+
+/// Directories of the crate in a registry index, keeping the case of its name
+/// (see https://doc.rust-lang.org/cargo/reference/registry-index.html#index-files)
+fn prefix(name: &str) -> String {
+    match name.len() {
+        1 => "1".to_string(),
+        2 => "2".to_string(),
+        3 => format!("3/{}", &name[..1]),
+        _ => format!("{}/{}", &name[..2], &name[2..4]),
+    }
+}
+
+/// Path of the crate in a registry index
+fn index_path(name: &str) -> String {
+    let name = name.to_lowercase();
+    format!("{}/{name}", prefix(&name))
+}
+
+fn download_url(dl: &str, name: &str, version: &str, checksum: &str) -> String {
+    const MARKERS: [&str; 5] = [
+        "{crate}",
+        "{version}",
+        "{prefix}",
+        "{lowerprefix}",
+        "{sha256-checksum}",
+    ];
+    if !MARKERS.iter().any(|m| dl.contains(m)) {
+        return format!("{}/{name}/{version}/download", dl.trim_end_matches('/'));
+    }
+
+    let prefix = prefix(name);
+    dl.replace("{crate}", name)
+        .replace("{version}", version)
+        .replace("{lowerprefix}", &prefix.to_lowercase())
+        .replace("{prefix}", &prefix)
+        .replace("{sha256-checksum}", checksum)
+}
+
+fn with_trailing_slash(url: &str) -> String {
+    format!("{}/", url.trim_end_matches('/'))
+}
+
+fn http_get_bytes(url: &str) -> Result<Vec<u8>> {
+    let response = attohttpc::get(url)
+        .send()
+        .with_context(|| format!("cannot reach {url}"))?;
+    if !response.is_success() {
+        return Err(anyhow!("cannot fetch {url}: {}", response.status()));
+    }
+    response
+        .bytes()
+        .with_context(|| format!("cannot read response from {url}"))
+}
+
+fn http_get_string(url: &str) -> Result<String> {
+    String::from_utf8(http_get_bytes(url)?).map_err(|_| anyhow!("invalid UTF-8 in {url}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::GzEncoder, Compression};
+    use tempfile::tempdir;
+
+    fn write_crate(registry: &Path, name: &str, version: &str, yanked: bool) {
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        let content = b"[template]\n";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(
+                &mut header,
+                format!("{name}-{version}/.scaffold.toml"),
+                &content[..],
+            )
+            .unwrap();
+        let archive = builder.into_inner().unwrap().finish().unwrap();
+        fs::write(registry.join(format!("{name}-{version}.crate")), &archive).unwrap();
+
+        let index_file = registry.join("index").join(index_path(name));
+        fs::create_dir_all(index_file.parent().unwrap()).unwrap();
+        let mut index = fs::read_to_string(&index_file).unwrap_or_default();
+        index.push_str(&format!(
+            "{{\"name\":\"{name}\",\"vers\":\"{version}\",\"deps\":[],\"cksum\":\"{:x}\",\"features\":{{}},\"yanked\":{yanked}}}\n",
+            Sha256::digest(&archive)
+        ));
+        fs::write(index_file, index).unwrap();
+    }
+
+    #[test]
+    fn parse_spec() {
+        let spec = CrateSpec::parse("our-service-template@1.4").unwrap();
+        assert_eq!(spec.name, "our-service-template");
+        assert_eq!(spec.version_req, VersionReq::parse("^1.4").unwrap());
+
+        let spec = CrateSpec::parse("our-service-template").unwrap();
+        assert_eq!(spec.version_req, VersionReq::STAR);
+
+        assert!(CrateSpec::parse("@1.0").is_err());
+        assert!(CrateSpec::parse("../../etc@1.0").is_err());
+        assert!(CrateSpec::parse("tëmplate").is_err());
+        assert!(CrateSpec::parse("template@not-a-version").is_err());
+    }
+
+    #[test]
+    fn registry_location() {
+        assert_eq!(
+            Registry::from_location(None),
+            Registry::Sparse("https://index.crates.io/".to_string())
+        );
+        assert_eq!(
+            Registry::from_location(Some("sparse+https://my.registry/index")),
+            Registry::Sparse("https://my.registry/index/".to_string())
+        );
+        assert_eq!(
+            Registry::from_location(Some("file:///srv/registry")),
+            Registry::Local(PathBuf::from("/srv/registry"))
+        );
+    }
+
+    #[test]
+    fn index_paths() {
+        assert_eq!(index_path("a"), "1/a");
+        assert_eq!(index_path("ab"), "2/ab");
+        assert_eq!(index_path("abc"), "3/a/abc");
+        assert_eq!(index_path("Cargo-Scaffold"), "ca/rg/cargo-scaffold");
+    }
+
+    #[test]
+    fn download_urls() {
+        assert_eq!(
+            download_url("https://static.crates.io/crates", "serde", "1.0.0", "abc"),
+            "https://static.crates.io/crates/serde/1.0.0/download"
+        );
+        assert_eq!(
+            download_url(
+                "https://my.registry/{prefix}/{crate}-{version}.crate",
+                "serde",
+                "1.0.0",
+                "abc"
+            ),
+            "https://my.registry/se/rd/serde-1.0.0.crate"
+        );
+        assert_eq!(
+            download_url(
+                "https://my.registry/{prefix}/{lowerprefix}/{crate}",
+                "Serde",
+                "1.0.0",
+                "abc"
+            ),
+            "https://my.registry/Se/rd/se/rd/Serde"
+        );
+    }
+
+    #[test]
+    fn fetch_from_local_registry() {
+        let registry = tempdir().unwrap();
+        write_crate(registry.path(), "my-template", "1.3.0", false);
+        write_crate(registry.path(), "my-template", "1.4.2", false);
+        write_crate(registry.path(), "my-template", "1.5.0", true);
+        write_crate(registry.path(), "my-template", "2.0.0", false);
+
+        let target = tempdir().unwrap();
+        let path = fetch("my-template@1.4", registry.path().to_str(), target.path()).unwrap();
+        assert_eq!(path, target.path().join("my-template-1.4.2"));
+        assert!(path.join(".scaffold.toml").exists());
+
+        let err = fetch("my-template@3", registry.path().to_str(), target.path()).unwrap_err();
+        assert!(err.to_string().contains("no version"));
+    }
+}
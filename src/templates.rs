@@ -0,0 +1,222 @@
+use anyhow::{anyhow, Context, Result};
+use console::Style;
+use dialoguer::Select;
+use serde::Deserialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
+
+use crate::SCAFFOLD_FILENAME;
+
+/// File listing the templates of a repository containing several ones
+pub const TEMPLATES_FILENAME: &str = "scaffold-templates.toml";
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TemplatesManifest {
+    templates: Vec<TemplateEntry>,
+}
+
+/// TemplateEntry: one of the templates of a repository
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct TemplateEntry {
+    /// Name given to `--template`
+    name: String,
+    /// Directory of the template, relative to the repository root
+    path: PathBuf,
+    description: Option<String>,
+}
+
+/// List the templates of the directory: the ones of its `scaffold-templates.toml` if any,
+/// otherwise the subdirectories containing a `.scaffold.toml`, named after their path
+pub(crate) fn list(root: &Path) -> Result<Vec<TemplateEntry>> {
+    let manifest_path = root.join(TEMPLATES_FILENAME);
+    if manifest_path.is_file() {
+        let content = fs::read_to_string(&manifest_path)
+            .with_context(|| format!("cannot read {manifest_path:?}"))?;
+        let manifest: TemplatesManifest = toml::from_str(&content)
+            .map_err(|e| anyhow!("invalid {TEMPLATES_FILENAME} : {}", e.message()))?;
+        return Ok(manifest.templates);
+    }
+
+    let mut templates = Vec::new();
+    let mut entries = WalkDir::new(root)
+        .min_depth(1)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| {
+            let name = entry.file_name().to_string_lossy();
+            entry.file_type().is_dir() && !name.starts_with('.') && name != "target"
+        });
+    while let Some(entry) = entries.next() {
+        let entry = entry.map_err(|e| anyhow!("cannot read entry : {}", e))?;
+        let scaffold_path = entry.path().join(SCAFFOLD_FILENAME);
+        if !scaffold_path.is_file() {
+            continue;
+        }
+        // The subdirectories of a template are part of it
+        entries.skip_current_dir();
+        let path = entry.path().strip_prefix(root)?.to_path_buf();
+        let description = fs::read_to_string(&scaffold_path)
+            .ok()
+            .and_then(|content| toml::from_str::<toml::Table>(&content).ok())
+            .and_then(|table| {
+                let description = table.get("template")?.get("description")?;
+                description.as_str().map(String::from)
+            });
+        templates.push(TemplateEntry {
+            name: path
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/"),
+            path,
+            description,
+        });
+    }
+
+    Ok(templates)
+}
+
+/// Directory of the template to use: the one named, the directory itself if it's a template,
+/// or the template chosen by the user among the ones of the directory
+pub(crate) fn select(root: &Path, name: Option<&str>) -> Result<PathBuf> {
+    if name.is_none() && root.join(SCAFFOLD_FILENAME).is_file() {
+        return Ok(root.to_path_buf());
+    }
+    let templates = list(root)?;
+    let names = || {
+        templates
+            .iter()
+            .map(|template| template.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    let template = match (name, templates.as_slice()) {
+        (Some(name), _) => templates
+            .iter()
+            .find(|template| template.name == name)
+            .ok_or_else(|| {
+                anyhow!(
+                    "there is no template named {name:?} in {root:?}, available templates are: {}",
+                    names()
+                )
+            })?,
+        // read_description reports the missing .scaffold.toml
+        (None, []) => return Ok(root.to_path_buf()),
+        (None, [template]) => template,
+        (None, _) if console::user_attended() => {
+            let dim = Style::new().dim();
+            let items = templates
+                .iter()
+                .map(|template| match &template.description {
+                    Some(description) => {
+                        format!("{} {}", template.name, dim.apply_to(description))
+                    }
+                    None => template.name.clone(),
+                })
+                .collect::<Vec<_>>();
+            let choice = Select::new()
+                .with_prompt("Which template do you want to use ?")
+                .items(&items)
+                .default(0)
+                .interact()?;
+            &templates[choice]
+        }
+        (None, _) => {
+            return Err(anyhow!(
+                "{root:?} contains several templates, choose one of them with --template: {}",
+                names()
+            ))
+        }
+    };
+
+    Ok(root.join(&template.path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn template(root: &Path, path: &str, description: &str) {
+        let dir = root.join(path);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join(SCAFFOLD_FILENAME),
+            format!("[template]\ndescription = {description:?}\n"),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn discovery() {
+        let dir = tempdir().unwrap();
+        template(dir.path(), "service", "An HTTP service");
+        template(dir.path(), "templates/cli", "A command line tool");
+        template(dir.path(), "service/nested", "Part of the service template");
+        template(dir.path(), ".github/template", "Hidden");
+
+        let templates = list(dir.path()).unwrap();
+        assert_eq!(
+            templates
+                .iter()
+                .map(|t| (t.name.as_str(), t.description.as_deref()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("service", Some("An HTTP service")),
+                ("templates/cli", Some("A command line tool")),
+            ]
+        );
+
+        assert_eq!(
+            select(dir.path(), Some("templates/cli")).unwrap(),
+            dir.path().join("templates").join("cli")
+        );
+        assert_eq!(
+            select(dir.path(), Some("library")).unwrap_err().to_string(),
+            format!(
+                "there is no template named \"library\" in {:?}, available templates are: service, templates/cli",
+                dir.path()
+            )
+        );
+        assert_eq!(
+            select(&dir.path().join("service"), None).unwrap(),
+            dir.path().join("service")
+        );
+    }
+
+    #[test]
+    fn manifest() {
+        let dir = tempdir().unwrap();
+        template(dir.path(), "templates/service", "An HTTP service");
+        template(dir.path(), "templates/library", "A library");
+        fs::write(
+            dir.path().join(TEMPLATES_FILENAME),
+            r#"[[templates]]
+name = "library"
+path = "templates/library"
+description = "A library crate"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            list(dir.path()).unwrap(),
+            vec![TemplateEntry {
+                name: "library".to_string(),
+                path: PathBuf::from("templates/library"),
+                description: Some("A library crate".to_string()),
+            }]
+        );
+        // a single template is used without asking
+        assert_eq!(
+            select(dir.path(), None).unwrap(),
+            dir.path().join("templates").join("library")
+        );
+    }
+}
@@ -0,0 +1,493 @@
+use anyhow::{anyhow, Context, Result};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use tempfile::TempDir;
+use walkdir::WalkDir;
+
+/// How the staged files are moved into the project directory
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Mode {
+    /// The project directory doesn't exist
+    Create,
+    /// The existing project directory is replaced (`--force`)
+    Replace,
+    /// The staged files are added to the existing project directory (`--append`), the existing
+    /// files are only staged when the user chose to replace them
+    Merge,
+    /// The staged files are added to the existing project directory, replacing the existing
+    /// ones, other files are kept (`--overwrite`)
+    Overwrite,
+}
+
+/// Transaction: the project is generated in a staging directory next to the project directory,
+/// and moved into place only once complete.
+///
+/// Until the transaction is finished, the project directory can be restored to its original
+/// state with `rollback`.
+pub(crate) struct Transaction {
+    project_path: PathBuf,
+    mode: Mode,
+    staging: TempDir,
+    committed: bool,
+    /// Previous project directory for a replacement, or the replaced files for an overwrite
+    backup: Option<PathBuf>,
+    /// Entries moved into the existing project directory, for a merge
+    merged: Vec<PathBuf>,
+    /// Files of the existing project directory replaced by staged ones, for an overwrite
+    replaced: Vec<PathBuf>,
+    /// Entries of the existing project directory which are never touched
+    protected: Vec<PathBuf>,
+    /// Files outside of the project directory which were edited, with their original content
+    edited: Vec<(PathBuf, String)>,
+}
+
+impl Transaction {
+    pub(crate) fn begin(project_path: &Path, mode: Mode) -> Result<Self> {
+        let parent = parent_dir(project_path);
+        fs::create_dir_all(parent).with_context(|| "Cannot create directory")?;
+        let staging = tempfile::Builder::new()
+            .prefix(&format!(".{}.scaffold-", file_name(project_path)))
+            .tempdir_in(parent)
+            .with_context(|| format!("Cannot create staging directory in {parent:?}"))?;
+
+        Ok(Self {
+            project_path: project_path.to_path_buf(),
+            mode,
+            staging,
+            committed: false,
+            backup: None,
+            merged: Vec::new(),
+            replaced: Vec::new(),
+            protected: Vec::new(),
+            edited: Vec::new(),
+        })
+    }
+
+    /// Keep these entries of the existing project directory (relative paths) as they are,
+    /// instead of the generated ones
+    pub(crate) fn protect(&mut self, paths: Vec<PathBuf>) {
+        self.protected = paths;
+    }
+
+    fn is_protected(&self, relative_path: &Path) -> bool {
+        self.protected
+            .iter()
+            .any(|protected| relative_path.starts_with(protected))
+    }
+
+    /// Directory in which the project is generated
+    pub(crate) fn staging_path(&self) -> &Path {
+        self.staging.path()
+    }
+
+    /// Move the generated files into the project directory
+    pub(crate) fn commit(&mut self) -> Result<()> {
+        match self.mode {
+            Mode::Create => fs::rename(self.staging.path(), &self.project_path)
+                .with_context(|| format!("Cannot create directory {:?}", self.project_path))?,
+            Mode::Replace => {
+                let backup = self.backup_path();
+                fs::rename(&self.project_path, &backup).with_context(|| {
+                    format!("Cannot move directory {:?} away", self.project_path)
+                })?;
+                if let Err(e) = fs::rename(self.staging.path(), &self.project_path) {
+                    fs::rename(&backup, &self.project_path).with_context(|| {
+                        format!("Cannot restore directory {:?}", self.project_path)
+                    })?;
+                    return Err(anyhow!(
+                        "cannot replace directory {:?} : {}",
+                        self.project_path,
+                        e
+                    ));
+                }
+                self.backup = Some(backup);
+                if let Err(e) = self.move_protected(true) {
+                    self.committed = true;
+                    self.rollback_replace()?;
+                    return Err(e);
+                }
+            }
+            Mode::Merge | Mode::Overwrite => {
+                if let Err(e) = self.merge() {
+                    self.rollback_merge()?;
+                    return Err(e);
+                }
+            }
+        }
+        self.committed = true;
+
+        Ok(())
+    }
+
+    /// Replace the content of a file outside of the project directory (i.e.: the manifest of
+    /// the enclosing workspace), restored on rollback
+    pub(crate) fn edit_outside(
+        &mut self,
+        path: &Path,
+        original: &str,
+        content: &str,
+    ) -> Result<()> {
+        let permissions = fs::metadata(path)
+            .map_err(|e| anyhow!("cannot get metadata for path : {}", e))?
+            .permissions();
+        crate::write_file(path, content.as_bytes(), permissions)?;
+        self.edited.push((path.to_path_buf(), original.to_string()));
+
+        Ok(())
+    }
+
+    fn backup_path(&self) -> PathBuf {
+        (0..)
+            .map(|i| {
+                parent_dir(&self.project_path).join(format!(
+                    ".{}.scaffold-backup-{}-{i}",
+                    file_name(&self.project_path),
+                    std::process::id()
+                ))
+            })
+            .find(|backup| !backup.exists())
+            .expect("an unused backup path. qed")
+    }
+
+    /// Move the protected entries from the previous project directory to the new one
+    /// (or back when `restore` is false)
+    fn move_protected(&self, restore: bool) -> Result<()> {
+        let Some(backup) = &self.backup else {
+            return Ok(());
+        };
+        for relative_path in &self.protected {
+            let (from, to) = if restore {
+                (
+                    backup.join(relative_path),
+                    self.project_path.join(relative_path),
+                )
+            } else {
+                (
+                    self.project_path.join(relative_path),
+                    backup.join(relative_path),
+                )
+            };
+            // Entries not moved yet are still in place
+            if from.symlink_metadata().is_err() || !restore && to.symlink_metadata().is_ok() {
+                continue;
+            }
+            remove_entry(&to)?;
+            if let Some(parent) = to.parent() {
+                fs::create_dir_all(parent).with_context(|| "Cannot create directory")?;
+            }
+            fs::rename(&from, &to)
+                .map_err(|e| anyhow!("cannot move protected {relative_path:?} : {}", e))?;
+        }
+
+        Ok(())
+    }
+
+    fn merge(&mut self) -> Result<()> {
+        let mut entries = WalkDir::new(self.staging.path())
+            .min_depth(1)
+            .sort_by_file_name()
+            .into_iter();
+        while let Some(entry) = entries.next() {
+            let entry = entry.map_err(|e| anyhow!("cannot read entry : {}", e))?;
+            let relative_path = entry.path().strip_prefix(self.staging.path())?;
+            let path = self.project_path.join(relative_path);
+            let exists = path.symlink_metadata().is_ok();
+            if exists && self.is_protected(relative_path) {
+                if entry.file_type().is_dir() {
+                    entries.skip_current_dir();
+                }
+                continue;
+            }
+            if entry.file_type().is_dir() {
+                if exists {
+                    continue;
+                }
+                // A new directory is moved with all its content
+                entries.skip_current_dir();
+            } else if exists {
+                // The replaced file is kept aside to be restored on rollback
+                let backup = match &self.backup {
+                    Some(backup) => backup.clone(),
+                    None => {
+                        let backup = self.backup_path();
+                        self.backup = Some(backup.clone());
+                        backup
+                    }
+                };
+                let backup = backup.join(relative_path);
+                if let Some(parent) = backup.parent() {
+                    fs::create_dir_all(parent).with_context(|| "Cannot create directory")?;
+                }
+                fs::rename(&path, &backup)
+                    .map_err(|e| anyhow!("cannot move {relative_path:?} away : {}", e))?;
+                self.replaced.push(relative_path.to_path_buf());
+            }
+            fs::rename(entry.path(), &path)
+                .map_err(|e| anyhow!("cannot move {relative_path:?} into place : {}", e))?;
+            if !exists {
+                self.merged.push(path);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn rollback_merge(&mut self) -> Result<()> {
+        for path in self.merged.drain(..).rev() {
+            remove_entry(&path)?;
+        }
+        if let Some(backup) = &self.backup {
+            for relative_path in self.replaced.drain(..).rev() {
+                let path = self.project_path.join(&relative_path);
+                remove_entry(&path)?;
+                fs::rename(backup.join(&relative_path), &path)
+                    .map_err(|e| anyhow!("cannot restore {relative_path:?} : {}", e))?;
+            }
+        }
+        if let Some(backup) = self.backup.take() {
+            fs::remove_dir_all(&backup).with_context(|| "Cannot remove directory")?;
+        }
+
+        Ok(())
+    }
+
+    fn rollback_replace(&mut self) -> Result<()> {
+        self.move_protected(false)?;
+        fs::remove_dir_all(&self.project_path).with_context(|| "Cannot remove directory")?;
+        if let Some(backup) = self.backup.take() {
+            fs::rename(&backup, &self.project_path)
+                .with_context(|| format!("Cannot restore directory {:?}", self.project_path))?;
+        }
+
+        Ok(())
+    }
+
+    /// Restore the project directory as it was before the transaction
+    pub(crate) fn rollback(mut self) -> Result<()> {
+        for (path, original) in self.edited.drain(..).rev() {
+            let permissions = fs::metadata(&path)
+                .map_err(|e| anyhow!("cannot get metadata for path : {}", e))?
+                .permissions();
+            crate::write_file(&path, original.as_bytes(), permissions)?;
+        }
+        if !self.committed {
+            // the staging directory is removed when dropped
+            return Ok(());
+        }
+        match self.mode {
+            Mode::Create => {
+                fs::remove_dir_all(&self.project_path).with_context(|| "Cannot remove directory")?
+            }
+            Mode::Replace => self.rollback_replace()?,
+            Mode::Merge | Mode::Overwrite => self.rollback_merge()?,
+        }
+
+        Ok(())
+    }
+
+    /// Remove the previous project directory (or the replaced files), the project can't be
+    /// restored anymore
+    pub(crate) fn finish(mut self) -> Result<()> {
+        if let Some(backup) = self.backup.take() {
+            fs::remove_dir_all(&backup).with_context(|| "Cannot remove directory")?;
+        }
+
+        Ok(())
+    }
+}
+
+fn remove_entry(path: &Path) -> Result<()> {
+    if path.symlink_metadata().is_err() {
+        return Ok(());
+    }
+    if path.is_dir() && !path.is_symlink() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+    .map_err(|e| anyhow!("cannot remove {path:?} : {}", e))
+}
+
+fn parent_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn stage(transaction: &Transaction, file: &str, content: &str) {
+        let path = transaction.staging_path().join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    fn files(dir: &Path) -> Vec<String> {
+        WalkDir::new(dir)
+            .min_depth(1)
+            .sort_by_file_name()
+            .into_iter()
+            .map(|e| {
+                e.unwrap()
+                    .path()
+                    .strip_prefix(dir)
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn create_and_rollback() {
+        let dir = tempdir().unwrap();
+        let project = dir.path().join("project");
+
+        let transaction = Transaction::begin(&project, Mode::Create).unwrap();
+        stage(&transaction, "src/main.rs", "fn main() {}");
+        drop(transaction);
+        assert!(!project.exists());
+        assert!(files(dir.path()).is_empty());
+
+        let mut transaction = Transaction::begin(&project, Mode::Create).unwrap();
+        stage(&transaction, "src/main.rs", "fn main() {}");
+        transaction.commit().unwrap();
+        assert_eq!(files(&project), vec!["src", "src/main.rs"]);
+        transaction.rollback().unwrap();
+        assert!(files(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn replace_and_rollback() {
+        let dir = tempdir().unwrap();
+        let project = dir.path().join("project");
+        fs::create_dir(&project).unwrap();
+        fs::write(project.join("old.txt"), "old").unwrap();
+
+        let mut transaction = Transaction::begin(&project, Mode::Replace).unwrap();
+        stage(&transaction, "new.txt", "new");
+        transaction.commit().unwrap();
+        assert_eq!(files(&project), vec!["new.txt"]);
+        transaction.rollback().unwrap();
+        assert_eq!(files(dir.path()), vec!["project", "project/old.txt"]);
+
+        let mut transaction = Transaction::begin(&project, Mode::Replace).unwrap();
+        stage(&transaction, "new.txt", "new");
+        transaction.commit().unwrap();
+        transaction.finish().unwrap();
+        assert_eq!(files(dir.path()), vec!["project", "project/new.txt"]);
+    }
+
+    #[test]
+    fn replace_keeps_protected_entries() {
+        let dir = tempdir().unwrap();
+        let project = dir.path().join("project");
+        fs::create_dir_all(project.join("target")).unwrap();
+        fs::write(project.join("target").join("build"), "").unwrap();
+        fs::write(project.join(".env"), "SECRET=1").unwrap();
+        fs::write(project.join("old.txt"), "old").unwrap();
+
+        let mut transaction = Transaction::begin(&project, Mode::Replace).unwrap();
+        transaction.protect(vec![PathBuf::from(".env"), PathBuf::from("target")]);
+        stage(&transaction, ".env", "SECRET=");
+        stage(&transaction, "new.txt", "new");
+        transaction.commit().unwrap();
+        assert_eq!(
+            files(&project),
+            vec![".env", "new.txt", "target", "target/build"]
+        );
+        assert_eq!(
+            fs::read_to_string(project.join(".env")).unwrap(),
+            "SECRET=1"
+        );
+        transaction.rollback().unwrap();
+        assert_eq!(
+            files(dir.path()),
+            vec![
+                "project",
+                "project/.env",
+                "project/old.txt",
+                "project/target",
+                "project/target/build"
+            ]
+        );
+    }
+
+    #[test]
+    fn overwrite_and_rollback() {
+        let dir = tempdir().unwrap();
+        let project = dir.path().join("project");
+        fs::create_dir_all(project.join("src")).unwrap();
+        fs::write(project.join("src").join("main.rs"), "old").unwrap();
+        fs::write(project.join(".env"), "SECRET=1").unwrap();
+        fs::write(project.join("notes.txt"), "").unwrap();
+
+        let mut transaction = Transaction::begin(&project, Mode::Overwrite).unwrap();
+        transaction.protect(vec![PathBuf::from(".env")]);
+        stage(&transaction, "src/main.rs", "new");
+        stage(&transaction, ".env", "SECRET=");
+        stage(&transaction, "README.md", "");
+        transaction.commit().unwrap();
+        assert_eq!(
+            files(&project),
+            vec![".env", "README.md", "notes.txt", "src", "src/main.rs"]
+        );
+        assert_eq!(
+            fs::read_to_string(project.join("src").join("main.rs")).unwrap(),
+            "new"
+        );
+        assert_eq!(
+            fs::read_to_string(project.join(".env")).unwrap(),
+            "SECRET=1"
+        );
+        transaction.rollback().unwrap();
+        assert_eq!(
+            files(dir.path()),
+            vec![
+                "project",
+                "project/.env",
+                "project/notes.txt",
+                "project/src",
+                "project/src/main.rs"
+            ]
+        );
+        assert_eq!(
+            fs::read_to_string(project.join("src").join("main.rs")).unwrap(),
+            "old"
+        );
+    }
+
+    #[test]
+    fn merge_and_rollback() {
+        let dir = tempdir().unwrap();
+        let project = dir.path().join("project");
+        fs::create_dir_all(project.join("src")).unwrap();
+        fs::write(project.join("src").join("main.rs"), "fn main() {}").unwrap();
+
+        let mut transaction = Transaction::begin(&project, Mode::Merge).unwrap();
+        stage(&transaction, "src/lib.rs", "");
+        stage(&transaction, "tests/it.rs", "");
+        transaction.commit().unwrap();
+        assert_eq!(
+            files(&project),
+            vec!["src", "src/lib.rs", "src/main.rs", "tests", "tests/it.rs"]
+        );
+        transaction.rollback().unwrap();
+        assert_eq!(
+            files(dir.path()),
+            vec!["project", "project/src", "project/src/main.rs"]
+        );
+    }
+}
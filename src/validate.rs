@@ -0,0 +1,873 @@
+use globset::Glob;
+use std::{fmt, ops::Range, path::Path};
+use toml_edit::{ImDocument, Item, TableLike, Value};
+
+use crate::{inject, patch};
+
+/// Keys allowed at the root of the scaffold file
+pub(crate) const ROOT_KEYS: &[&str] = &["template", "parameters", "hooks", "inject", "patch"];
+/// Keys allowed in the `[template]` table
+pub(crate) const TEMPLATE_KEYS: &[&str] = &[
+    "name",
+    "author",
+    "version",
+    "description",
+    "homepage",
+    "min_scaffold_version",
+    "extends",
+    "partials",
+    "helpers",
+    "exclude",
+    "disable_templating",
+    "protected",
+    "notes",
+    "delimiters",
+    "modes",
+];
+/// Keys allowed in the `extends` table of a template from a git repository
+pub(crate) const EXTENDS_KEYS: &[&str] = &["git", "ref", "path"];
+/// Keys allowed in each `[[template.delimiters]]` table
+pub(crate) const DELIMITERS_KEYS: &[&str] = &["glob", "open", "close"];
+/// Keys allowed in each `[[template.modes]]` table
+pub(crate) const MODES_KEYS: &[&str] = &["glob", "mode"];
+/// Keys allowed in each `[[inject]]` table
+pub(crate) const INJECT_KEYS: &[&str] =
+    &["file", "content", "before", "after", "marker", "skip_if"];
+/// Keys allowed in each `[[patch]]` table
+pub(crate) const PATCH_KEYS: &[&str] = &["file", "op", "key", "value"];
+/// Patch operations, as written in the scaffold file
+pub(crate) const PATCH_OPERATIONS: &[&str] = &["set", "append", "merge"];
+/// Keys allowed in each `[parameters.<name>]` table
+pub(crate) const PARAMETER_KEYS: &[&str] =
+    &["message", "required", "type", "default", "values", "tags"];
+/// Keys allowed in the `[hooks]` table
+pub(crate) const HOOKS_KEYS: &[&str] = &["pre", "post"];
+/// Parameter types, as written in the scaffold file
+pub(crate) const PARAMETER_TYPES: &[&str] = &[
+    "string",
+    "integer",
+    "float",
+    "boolean",
+    "select",
+    "multiselect",
+];
+/// Parameters set by cargo-scaffold itself
+pub(crate) const RESERVED_PARAMETERS: &[&str] = &[
+    "name",
+    "target_dir",
+    "workspace_root",
+    "workspace_version",
+    "workspace_edition",
+];
+
+/// Diagnostic: a problem found in a scaffold file, with its position.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    /// Line of the problem, starting at 1
+    pub line: usize,
+    /// Column of the problem, starting at 1
+    pub column: usize,
+    pub message: String,
+    /// The problem is a key unknown to this version of cargo-scaffold
+    pub unknown_key: bool,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+type TableWithSpan<'a> = (&'a dyn TableLike, Option<Range<usize>>);
+
+struct Checker<'a> {
+    content: &'a str,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Check the content of a scaffold file, returning all the problems found
+pub(crate) fn check(content: &str) -> Vec<Diagnostic> {
+    let mut checker = Checker {
+        content,
+        diagnostics: Vec::new(),
+    };
+
+    let document = match ImDocument::parse(content) {
+        Ok(document) => document,
+        Err(e) => {
+            checker.error(e.span(), e.message().trim().to_string());
+            return checker.diagnostics;
+        }
+    };
+
+    let root = document.as_table();
+    checker.check_keys(root, "", ROOT_KEYS);
+    match root.get("template").and_then(Item::as_table_like) {
+        Some(template) => checker.check_template(template),
+        None => checker.error(Some(0..0), "missing [template] table".to_string()),
+    }
+    if let Some(hooks) = checker.table(root, "hooks") {
+        checker.check_keys(hooks, "hooks", HOOKS_KEYS);
+        for key in HOOKS_KEYS {
+            checker.check_string_array(hooks, key);
+        }
+    }
+    if let Some(item) = root.get("inject") {
+        checker.check_injections(item);
+    }
+    if let Some(item) = root.get("patch") {
+        checker.check_patches(item);
+    }
+    if let Some(parameters) = checker.table(root, "parameters") {
+        for (name, item) in parameters.iter() {
+            let key_span = parameters.key(name).and_then(|k| k.span());
+            if RESERVED_PARAMETERS.contains(&name) {
+                checker.error(
+                    key_span.clone(),
+                    format!("parameter name {name:?} is reserved"),
+                );
+            }
+            match item.as_table_like() {
+                Some(parameter) => checker.check_parameter(name, parameter, key_span),
+                None => checker.error(
+                    item.span().or(key_span),
+                    format!("parameters.{name} must be a table"),
+                ),
+            }
+        }
+    }
+
+    checker.diagnostics.sort_by_key(|d| (d.line, d.column));
+    checker.diagnostics
+}
+
+impl Checker<'_> {
+    fn error(&mut self, span: Option<Range<usize>>, message: String) {
+        self.push(span, message, false);
+    }
+
+    fn push(&mut self, span: Option<Range<usize>>, message: String, unknown_key: bool) {
+        let offset = span.map(|s| s.start).unwrap_or_default();
+        let (line, column) = position(self.content, offset);
+        self.diagnostics.push(Diagnostic {
+            line,
+            column,
+            message,
+            unknown_key,
+        });
+    }
+
+    fn table<'t>(&mut self, parent: &'t dyn TableLike, key: &str) -> Option<&'t dyn TableLike> {
+        let item = parent.get(key)?;
+        let table = item.as_table_like();
+        if table.is_none() {
+            self.error(item.span(), format!("{key} must be a table"));
+        }
+        table
+    }
+
+    fn check_keys(&mut self, table: &dyn TableLike, path: &str, known_keys: &[&str]) {
+        for (key, _) in table.iter() {
+            if known_keys.contains(&key) {
+                continue;
+            }
+            let location = if path.is_empty() {
+                String::new()
+            } else {
+                format!(" in [{path}]")
+            };
+            let suggestion = known_keys
+                .iter()
+                .filter(|known| edit_distance(known, key) <= 2)
+                .min_by_key(|known| edit_distance(known, key))
+                .map(|known| format!(", did you mean `{known}`?"))
+                .unwrap_or_default();
+            self.push(
+                table.key(key).and_then(|k| k.span()),
+                format!("unknown key `{key}`{location}{suggestion}"),
+                true,
+            );
+        }
+    }
+
+    fn check_string(&mut self, table: &dyn TableLike, key: &str) {
+        if let Some(item) = table.get(key) {
+            if item.as_str().is_none() {
+                self.error(item.span(), format!("`{key}` must be a string"));
+            }
+        }
+    }
+
+    /// The edited file must be inside of the generated project
+    fn check_file(&mut self, table: &dyn TableLike) {
+        let Some(item) = table.get("file") else {
+            return;
+        };
+        if let Some(file) = item.as_str() {
+            if !crate::is_project_relative(Path::new(file)) {
+                self.error(
+                    item.span(),
+                    "`file` must be relative to the project directory, without `..`".to_string(),
+                );
+            }
+        }
+    }
+
+    fn check_string_array<'t>(
+        &mut self,
+        table: &'t dyn TableLike,
+        key: &str,
+    ) -> Vec<(&'t str, Option<Range<usize>>)> {
+        let Some(item) = table.get(key) else {
+            return Vec::new();
+        };
+        let strings = item
+            .as_array()
+            .map(|array| {
+                array
+                    .iter()
+                    .map(|v| v.as_str().map(|s| (s, v.span())))
+                    .collect::<Option<Vec<_>>>()
+            })
+            .unwrap_or_default();
+        match strings {
+            Some(strings) => strings,
+            None => {
+                self.error(item.span(), format!("`{key}` must be an array of strings"));
+                Vec::new()
+            }
+        }
+    }
+
+    fn check_template(&mut self, template: &dyn TableLike) {
+        self.check_keys(template, "template", TEMPLATE_KEYS);
+        for key in [
+            "name",
+            "author",
+            "version",
+            "description",
+            "homepage",
+            "min_scaffold_version",
+            "partials",
+            "helpers",
+            "notes",
+        ] {
+            self.check_string(template, key);
+        }
+        if let Some(item) = template.get("extends") {
+            self.check_extends(item);
+        }
+        for key in ["exclude", "disable_templating", "protected"] {
+            for (glob, span) in self.check_string_array(template, key) {
+                if let Err(e) = Glob::new(glob.trim_start_matches("./")) {
+                    self.error(span, format!("invalid glob in `{key}`: {}", e.kind()));
+                }
+            }
+        }
+        if let Some(item) = template.get("delimiters") {
+            self.check_delimiters(item);
+        }
+        if let Some(item) = template.get("modes") {
+            self.check_modes(item);
+        }
+    }
+
+    /// The parent template is a string, or a table for a git repository
+    fn check_extends(&mut self, item: &Item) {
+        if item.is_str() {
+            return;
+        }
+        let Some(table) = item.as_table_like() else {
+            self.error(
+                item.span(),
+                "`extends` must be a string or a table".to_string(),
+            );
+            return;
+        };
+        self.check_keys(table, "template.extends", EXTENDS_KEYS);
+        for key in EXTENDS_KEYS {
+            self.check_string(table, key);
+        }
+        if table.get("git").is_none() {
+            self.error(item.span(), "extends is missing a `git`".to_string());
+        }
+    }
+
+    /// Tables of an array of tables like `[[template.delimiters]]`, with their span
+    fn array_of_tables<'t>(&mut self, item: &'t Item, key: &str) -> Vec<TableWithSpan<'t>> {
+        let tables: Option<Vec<TableWithSpan>> = match (item.as_array_of_tables(), item.as_array())
+        {
+            (Some(tables), _) => tables
+                .iter()
+                .map(|t| Some((t as &dyn TableLike, t.span())))
+                .collect(),
+            (_, Some(array)) => array
+                .iter()
+                .map(|v| v.as_inline_table().map(|t| (t as &dyn TableLike, v.span())))
+                .collect(),
+            _ => None,
+        };
+        tables.unwrap_or_else(|| {
+            self.error(item.span(), format!("`{key}` must be an array of tables"));
+            Vec::new()
+        })
+    }
+
+    /// Check the keys of a table selecting files with a glob, which are all required
+    fn check_glob_table(&mut self, (table, span): TableWithSpan, key: &str, known_keys: &[&str]) {
+        self.check_keys(table, &format!("template.{key}"), known_keys);
+        for known_key in known_keys {
+            if table.get(known_key).is_none() {
+                self.error(span.clone(), format!("{key} are missing a `{known_key}`"));
+            }
+        }
+        if let Some(item) = table.get("glob") {
+            match item
+                .as_str()
+                .map(|glob| Glob::new(glob.trim_start_matches("./")))
+            {
+                Some(Ok(_)) => {}
+                Some(Err(e)) => self.error(
+                    item.span(),
+                    format!("invalid glob in `{key}`: {}", e.kind()),
+                ),
+                None => self.error(item.span(), "`glob` must be a string".to_string()),
+            }
+        }
+    }
+
+    fn check_delimiters(&mut self, item: &Item) {
+        for (delimiters, span) in self.array_of_tables(item, "delimiters") {
+            self.check_glob_table((delimiters, span), "delimiters", DELIMITERS_KEYS);
+            for key in ["open", "close"] {
+                match delimiters.get(key) {
+                    Some(item) if item.as_str().is_some_and(str::is_empty) => {
+                        self.error(item.span(), format!("`{key}` must not be empty"));
+                    }
+                    _ => self.check_string(delimiters, key),
+                }
+            }
+        }
+    }
+
+    fn check_modes(&mut self, item: &Item) {
+        for (mode, span) in self.array_of_tables(item, "modes") {
+            self.check_glob_table((mode, span), "modes", MODES_KEYS);
+            if let Some(item) = mode.get("mode") {
+                if !item
+                    .as_integer()
+                    .is_some_and(|mode| (0..=0o7777).contains(&mode))
+                {
+                    self.error(
+                        item.span(),
+                        "`mode` must be an octal integer like 0o755".to_string(),
+                    );
+                }
+            }
+        }
+    }
+
+    fn check_injections(&mut self, item: &Item) {
+        for (injection, span) in self.array_of_tables(item, "inject") {
+            self.check_keys(injection, "inject", INJECT_KEYS);
+            for key in INJECT_KEYS {
+                self.check_string(injection, key);
+            }
+            self.check_file(injection);
+            for key in ["file", "content"] {
+                if injection.get(key).is_none() {
+                    self.error(span.clone(), format!("inject is missing a `{key}`"));
+                }
+            }
+            let anchors = ["before", "after", "marker"]
+                .into_iter()
+                .filter(|key| injection.get(key).is_some())
+                .collect::<Vec<_>>();
+            if anchors.len() > 1 {
+                self.error(
+                    span.clone(),
+                    "only one of `before`, `after` and `marker` can be set".to_string(),
+                );
+            }
+            for key in ["before", "after", "skip_if"] {
+                let Some(item) = injection.get(key) else {
+                    continue;
+                };
+                if let Some(Err(e)) = item.as_str().map(regex::Regex::new) {
+                    self.error(
+                        item.span(),
+                        format!("invalid regex in `{key}`: {}", inject::regex_error(&e)),
+                    );
+                }
+            }
+        }
+    }
+
+    fn check_patches(&mut self, item: &Item) {
+        for (patch, span) in self.array_of_tables(item, "patch") {
+            self.check_keys(patch, "patch", PATCH_KEYS);
+            for key in PATCH_KEYS {
+                if patch.get(key).is_none() {
+                    self.error(span.clone(), format!("patch is missing a `{key}`"));
+                }
+            }
+            for key in ["file", "op", "key"] {
+                self.check_string(patch, key);
+            }
+            self.check_file(patch);
+            let file = patch
+                .get("file")
+                .and_then(|item| item.as_str().map(|f| (item, f)));
+            if let Some((item, file)) = file {
+                if patch::Format::of(Path::new(file)).is_none() {
+                    self.error(
+                        item.span(),
+                        "only TOML and JSON files can be patched".to_string(),
+                    );
+                }
+            }
+            let op = patch
+                .get("op")
+                .and_then(|item| item.as_str().map(|op| (item, op)));
+            if let Some((item, op)) = op {
+                if !PATCH_OPERATIONS.contains(&op) {
+                    self.error(
+                        item.span(),
+                        format!(
+                            "invalid patch operation {op:?}, expected one of: {}",
+                            PATCH_OPERATIONS.join(", ")
+                        ),
+                    );
+                }
+            }
+            if let Some(item) = patch.get("key") {
+                if let Some(Err(e)) = item.as_str().map(patch::parse_key) {
+                    self.error(item.span(), e.to_string());
+                }
+            }
+            if let (Some((_, "merge")), Some(value)) = (op, patch.get("value")) {
+                if !value.is_table_like() {
+                    self.error(
+                        value.span(),
+                        "the value to merge must be a table".to_string(),
+                    );
+                }
+            }
+        }
+    }
+
+    fn check_parameter(
+        &mut self,
+        name: &str,
+        parameter: &dyn TableLike,
+        key_span: Option<Range<usize>>,
+    ) {
+        self.check_keys(parameter, &format!("parameters.{name}"), PARAMETER_KEYS);
+        self.check_string(parameter, "message");
+        self.check_string_array(parameter, "tags");
+        if parameter.get("message").is_none() {
+            self.error(
+                key_span.clone(),
+                format!("parameter {name:?} is missing a `message`"),
+            );
+        }
+        if let Some(required) = parameter.get("required") {
+            if required.as_bool().is_none() {
+                self.error(required.span(), "`required` must be a boolean".to_string());
+            }
+        }
+
+        let r#type = match parameter.get("type") {
+            Some(item) => match item.as_str() {
+                Some(r#type) if PARAMETER_TYPES.contains(&r#type) => r#type,
+                _ => {
+                    self.error(
+                        item.span(),
+                        format!(
+                            "invalid type for parameter {name:?}, expected one of: {}",
+                            PARAMETER_TYPES.join(", ")
+                        ),
+                    );
+                    return;
+                }
+            },
+            None => {
+                self.error(key_span, format!("parameter {name:?} is missing a `type`"));
+                return;
+            }
+        };
+
+        let values = parameter.get("values").map(|item| (item, item.as_array()));
+        if let Some((item, None)) = values {
+            self.error(item.span(), "`values` must be an array".to_string());
+        }
+        let values = values.and_then(|(_, values)| values);
+        let is_select = matches!(r#type, "select" | "multiselect");
+        if is_select && values.is_none_or(|v| v.is_empty()) {
+            self.error(
+                parameter.get("values").and_then(Item::span).or(key_span),
+                format!("{type} parameter {name:?} must have non-empty `values`"),
+            );
+        }
+
+        let Some(default) = parameter.get("default") else {
+            return;
+        };
+        let Some(default_value) = default.as_value() else {
+            self.error(default.span(), "`default` must be a value".to_string());
+            return;
+        };
+        let type_matches = match r#type {
+            "string" => default_value.is_str(),
+            "integer" => default_value.is_integer(),
+            "float" => default_value.is_float() || default_value.is_integer(),
+            "boolean" => default_value.is_bool(),
+            "select" => true,
+            _ => default_value.is_array(),
+        };
+        if !type_matches {
+            self.error(
+                default.span(),
+                format!("default value of parameter {name:?} doesn't match its type {type}"),
+            );
+            return;
+        }
+
+        if let (true, Some(values)) = (is_select, values) {
+            let defaults = match default_value.as_array() {
+                Some(defaults) => defaults.iter().collect(),
+                None => vec![default_value],
+            };
+            for default in defaults {
+                if !values.iter().any(|v| same_value(v, default)) {
+                    self.error(
+                        default.span().or(parameter.get("default").and_then(Item::span)),
+                        format!("default value {default} of parameter {name:?} is not one of its `values`"),
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn same_value(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::String(a), Value::String(b)) => a.value() == b.value(),
+        (Value::Integer(a), Value::Integer(b)) => a.value() == b.value(),
+        (Value::Float(a), Value::Float(b)) => a.value() == b.value(),
+        (Value::Boolean(a), Value::Boolean(b)) => a.value() == b.value(),
+        (a, b) => a.to_string().trim() == b.to_string().trim(),
+    }
+}
+
+/// Line and column (starting at 1) of the byte offset in the content
+fn position(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .rsplit('\n')
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .count()
+        + 1;
+    (line, column)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(content: &str) -> Vec<String> {
+        check(content).iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn keys_match_the_json_schema() {
+        let schema: serde_json::Value =
+            serde_json::from_str(&crate::ScaffoldDescription::json_schema()).unwrap();
+        let keys = |pointer: &str| {
+            let mut keys = schema
+                .pointer(pointer)
+                .and_then(|p| p.as_object())
+                .unwrap()
+                .keys()
+                .cloned()
+                .collect::<Vec<_>>();
+            keys.sort();
+            keys
+        };
+        let sorted = |keys: &[&str]| {
+            let mut keys = keys.iter().map(ToString::to_string).collect::<Vec<_>>();
+            keys.sort();
+            keys
+        };
+
+        assert_eq!(keys("/properties"), sorted(ROOT_KEYS));
+        assert_eq!(
+            keys("/$defs/TemplateDescription/properties"),
+            sorted(TEMPLATE_KEYS)
+        );
+        assert_eq!(keys("/$defs/Parameter/properties"), sorted(PARAMETER_KEYS));
+        assert_eq!(keys("/$defs/Hooks/properties"), sorted(HOOKS_KEYS));
+        assert_eq!(
+            keys("/$defs/Delimiters/properties"),
+            sorted(DELIMITERS_KEYS)
+        );
+        assert_eq!(keys("/$defs/FileMode/properties"), sorted(MODES_KEYS));
+        assert_eq!(keys("/$defs/Injection/properties"), sorted(INJECT_KEYS));
+        assert_eq!(keys("/$defs/Patch/properties"), sorted(PATCH_KEYS));
+        assert_eq!(keys("/$defs/GitTemplate/properties"), sorted(EXTENDS_KEYS));
+        assert_eq!(
+            schema.pointer("/$defs/PatchOperation/enum"),
+            Some(&serde_json::json!(PATCH_OPERATIONS))
+        );
+        assert_eq!(
+            schema.pointer("/$defs/ParameterType/enum"),
+            Some(&serde_json::json!(PARAMETER_TYPES))
+        );
+    }
+
+    #[test]
+    fn valid_scaffold_file() {
+        let content = include_str!("../examples/.scaffold.toml");
+        assert_eq!(check(content), vec![]);
+    }
+
+    #[test]
+    fn unknown_keys() {
+        let content = r#"[template]
+disable_templatng = ["*.png"]
+
+[parameters.feature]
+type = "string"
+message = "Feature ?"
+requird = true
+"#;
+        let diagnostics = check(content);
+        assert!(diagnostics.iter().all(|d| d.unknown_key));
+        assert_eq!(
+            messages(content),
+            vec![
+                "2:1: unknown key `disable_templatng` in [template], did you mean `disable_templating`?",
+                "7:1: unknown key `requird` in [parameters.feature], did you mean `required`?",
+            ]
+        );
+    }
+
+    #[test]
+    fn semantic_problems() {
+        let content = r#"[template]
+exclude = ["target/[a"]
+
+[parameters.name]
+type = "string"
+message = "Name ?"
+
+[parameters.kind]
+type = "select"
+message = "Kind ?"
+
+[parameters.api]
+type = "select"
+message = "API ?"
+values = ["REST", "graphql"]
+default = "grpc"
+
+[parameters.limit]
+type = "integer"
+message = "Limit ?"
+default = "ten"
+
+[parameters.color]
+type = "colour"
+message = "Color ?"
+"#;
+        assert_eq!(
+            messages(content),
+            vec![
+                "2:12: invalid glob in `exclude`: unclosed character class; missing ']'",
+                "4:13: parameter name \"name\" is reserved",
+                "8:13: select parameter \"kind\" must have non-empty `values`",
+                "16:11: default value \"grpc\" of parameter \"api\" is not one of its `values`",
+                "21:11: default value of parameter \"limit\" doesn't match its type integer",
+                "24:8: invalid type for parameter \"color\", expected one of: string, integer, float, boolean, select, multiselect",
+            ]
+        );
+    }
+
+    #[test]
+    fn extends() {
+        assert!(messages("[template]\nextends = \"../base\"\n").is_empty());
+        assert!(messages(
+            "[template]\nextends = { git = \"https://github.com/acme/templates.git\", ref = \"^1\", path = \"base\" }\n"
+        )
+        .is_empty());
+        assert_eq!(
+            messages("[template]\nextends = { ref = \"^1\", branch = \"main\" }\n"),
+            vec![
+                "2:11: extends is missing a `git`",
+                "2:25: unknown key `branch` in [template.extends]",
+            ]
+        );
+        assert_eq!(
+            messages("[template]\nextends = 1\n"),
+            vec!["2:11: `extends` must be a string or a table"]
+        );
+    }
+
+    #[test]
+    fn delimiters() {
+        let content = r#"[template]
+
+[[template.delimiters]]
+glob = "*.yml"
+open = ""
+
+[[template.delimiters]]
+glob = "[a"
+open = "<%"
+close = "%>"
+"#;
+        assert_eq!(
+            messages(content),
+            vec![
+                "3:1: delimiters are missing a `close`",
+                "5:8: `open` must not be empty",
+                "8:8: invalid glob in `delimiters`: unclosed character class; missing ']'",
+            ]
+        );
+        assert_eq!(
+            messages(
+                "[template]\ndelimiters = [{ glob = \"*.vue\", open = \"<%\", close = \"%>\" }]\n"
+            ),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            messages("[template]\ndelimiters = \"<%\"\n"),
+            vec!["2:14: `delimiters` must be an array of tables"]
+        );
+    }
+
+    #[test]
+    fn modes() {
+        let content = r#"[template]
+modes = [
+    { glob = "bin/*", mode = 0o755 },
+    { glob = "secrets/*", mode = "600" },
+    { mode = 0o600 },
+]
+"#;
+        assert_eq!(
+            messages(content),
+            vec![
+                "4:34: `mode` must be an octal integer like 0o755",
+                "5:5: modes are missing a `glob`",
+            ]
+        );
+    }
+
+    #[test]
+    fn injections() {
+        let content = r#"[template]
+
+[[inject]]
+file = "src/lib.rs"
+content = "mod {{name}};"
+after = "^mod "
+
+[[inject]]
+file = "src/main.rs"
+before = "^fn main"
+marker = "// scaffold:mods"
+
+[[inject]]
+file = "src/router.rs"
+content = "route"
+skip_if = "(route"
+
+[[inject]]
+file = "../other/src/lib.rs"
+content = "mod {{name}};"
+"#;
+        assert_eq!(
+            messages(content),
+            vec![
+                "8:1: inject is missing a `content`",
+                "8:1: only one of `before`, `after` and `marker` can be set",
+                "16:11: invalid regex in `skip_if`: unclosed group",
+                "19:8: `file` must be relative to the project directory, without `..`",
+            ]
+        );
+        assert_eq!(
+            messages("inject = { file = \"src/lib.rs\" }\n[template]\n"),
+            vec!["1:10: `inject` must be an array of tables"]
+        );
+    }
+
+    #[test]
+    fn patches() {
+        let content = r#"[template]
+
+[[patch]]
+file = "Cargo.toml"
+op = "append"
+key = "workspace.members"
+value = "{{name}}"
+
+[[patch]]
+file = "Dockerfile"
+op = "replace"
+key = "workspace..members"
+
+[[patch]]
+file = "package.json"
+op = "merge"
+key = "scripts"
+value = "vitest"
+
+[[patch]]
+file = "/etc/config.toml"
+op = "set"
+key = "debug"
+value = true
+"#;
+        assert_eq!(
+            messages(content),
+            vec![
+                "9:1: patch is missing a `value`",
+                "10:8: only TOML and JSON files can be patched",
+                "11:6: invalid patch operation \"replace\", expected one of: set, append, merge",
+                "12:7: invalid key \"workspace..members\"",
+                "18:9: the value to merge must be a table",
+                "21:8: `file` must be relative to the project directory, without `..`",
+            ]
+        );
+    }
+
+    #[test]
+    fn syntax_error() {
+        let diagnostics = check("[template]\nname = \n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!((diagnostics[0].line, diagnostics[0].column), (2, 8));
+    }
+
+    #[test]
+    fn missing_template() {
+        assert_eq!(messages("[hooks]\n"), vec!["1:1: missing [template] table"]);
+    }
+}
@@ -0,0 +1,217 @@
+use anyhow::{anyhow, Result};
+use globset::GlobBuilder;
+use indexmap::IndexMap;
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::{
+    patch::{self, Format},
+    PatchOperation, Value,
+};
+
+const MANIFEST_FILENAME: &str = "Cargo.toml";
+
+/// Workspace: the closest cargo workspace enclosing a project directory
+pub(crate) struct Workspace {
+    root: PathBuf,
+    manifest: String,
+    table: toml::Table,
+}
+
+/// Whether a crate is registered in the workspace members
+#[derive(Debug, PartialEq)]
+pub(crate) enum Membership {
+    Member,
+    Excluded,
+    /// Not a member yet, with the manifest listing it in the members
+    Missing(String),
+}
+
+impl Workspace {
+    /// Look for a Cargo.toml with a `[workspace]` table in the ancestors of the project directory
+    pub(crate) fn find(project_path: &Path) -> Result<Option<Self>> {
+        for dir in project_path.ancestors().skip(1) {
+            let manifest_path = dir.join(MANIFEST_FILENAME);
+            if !manifest_path.is_file() {
+                continue;
+            }
+            let manifest = fs::read_to_string(&manifest_path)
+                .map_err(|e| anyhow!("cannot read file {manifest_path:?} : {}", e))?;
+            let table: toml::Table = toml::from_str(&manifest)
+                .map_err(|e| anyhow!("invalid manifest {manifest_path:?} : {}", e.message()))?;
+            if table.contains_key("workspace") {
+                return Ok(Some(Self {
+                    root: dir.to_path_buf(),
+                    manifest,
+                    table,
+                }));
+            }
+        }
+
+        Ok(None)
+    }
+
+    pub(crate) fn manifest_path(&self) -> PathBuf {
+        self.root.join(MANIFEST_FILENAME)
+    }
+
+    pub(crate) fn manifest(&self) -> &str {
+        &self.manifest
+    }
+
+    fn get(&self, key: &[&str]) -> Option<&Value> {
+        let (first, rest) = key.split_first()?;
+        rest.iter()
+            .try_fold(self.table.get(*first)?, |value, key| value.get(key))
+    }
+
+    /// Template variables describing the workspace: its root, and the version and edition
+    /// inherited by its crates
+    pub(crate) fn parameters(&self) -> IndexMap<String, Value> {
+        let mut parameters = IndexMap::new();
+        parameters.insert(
+            "workspace_root".to_string(),
+            Value::String(self.root.to_string_lossy().to_string()),
+        );
+        for name in ["version", "edition"] {
+            if let Some(value) = self.get(&["workspace", "package", name]) {
+                parameters.insert(format!("workspace_{name}"), value.clone());
+            }
+        }
+        parameters
+    }
+
+    /// Path of the crate relative to the workspace root, with `/` separators
+    pub(crate) fn relative_path(&self, crate_path: &Path) -> Option<String> {
+        let relative = crate_path.strip_prefix(&self.root).ok()?;
+        let components = relative
+            .components()
+            .map(|component| match component {
+                Component::Normal(part) => part.to_str(),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+        (!components.is_empty()).then(|| components.join("/"))
+    }
+
+    /// Check if the crate is in the excluded paths or matched by the members globs,
+    /// like cargo does
+    pub(crate) fn membership(&self, relative_path: &str) -> Result<Membership> {
+        let paths = |key: &str| {
+            self.get(&["workspace", key])
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .map(|path| path.trim_start_matches("./").trim_end_matches('/'))
+        };
+
+        if paths("exclude").any(|excluded| Path::new(relative_path).starts_with(excluded)) {
+            return Ok(Membership::Excluded);
+        }
+        for member in paths("members") {
+            let glob = GlobBuilder::new(member)
+                .literal_separator(true)
+                .build()
+                .map_err(|e| anyhow!("invalid workspace member {member:?} : {}", e.kind()))?;
+            if glob.compile_matcher().is_match(relative_path) {
+                return Ok(Membership::Member);
+            }
+        }
+
+        let manifest = patch::patch(
+            Format::Toml,
+            &self.manifest,
+            PatchOperation::Append,
+            &patch::parse_key("workspace.members")?,
+            &Value::String(relative_path.to_string()),
+        )?;
+        Ok(manifest.map_or(Membership::Member, Membership::Missing))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    const MANIFEST: &str = r#"[workspace]
+members = [
+    "cli",
+    "crates/*",
+]
+exclude = ["crates/legacy", "examples"]
+
+[workspace.package]
+version = "1.2.0"
+edition = "2021"
+"#;
+
+    #[test]
+    fn find_and_parameters() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("repo");
+        fs::create_dir_all(root.join("crates").join("core")).unwrap();
+        fs::write(root.join("Cargo.toml"), MANIFEST).unwrap();
+        fs::write(
+            root.join("crates").join("core").join("Cargo.toml"),
+            "[package]\nname = \"core\"\n",
+        )
+        .unwrap();
+
+        assert!(Workspace::find(dir.path()).unwrap().is_none());
+        let workspace = Workspace::find(&root.join("crates").join("core").join("api"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(workspace.root, root);
+        let parameters = workspace.parameters();
+        assert_eq!(
+            parameters.get("workspace_root"),
+            Some(&Value::String(root.to_string_lossy().to_string()))
+        );
+        assert_eq!(
+            parameters.get("workspace_version"),
+            Some(&Value::String("1.2.0".to_string()))
+        );
+        assert_eq!(
+            parameters.get("workspace_edition"),
+            Some(&Value::String("2021".to_string()))
+        );
+        assert_eq!(
+            workspace.relative_path(&root.join("crates").join("api")),
+            Some("crates/api".to_string())
+        );
+        assert_eq!(workspace.relative_path(&root), None);
+    }
+
+    #[test]
+    fn membership() {
+        let workspace = Workspace {
+            root: PathBuf::from("/repo"),
+            manifest: MANIFEST.to_string(),
+            table: toml::from_str(MANIFEST).unwrap(),
+        };
+        assert_eq!(workspace.membership("cli").unwrap(), Membership::Member);
+        assert_eq!(
+            workspace.membership("crates/api").unwrap(),
+            Membership::Member
+        );
+        assert_eq!(
+            workspace.membership("crates/legacy").unwrap(),
+            Membership::Excluded
+        );
+        assert_eq!(
+            workspace.membership("examples/demo").unwrap(),
+            Membership::Excluded
+        );
+        assert_eq!(
+            workspace.membership("services/api").unwrap(),
+            Membership::Missing(MANIFEST.replace(
+                "    \"crates/*\",\n",
+                "    \"crates/*\",\n    \"services/api\",\n"
+            ))
+        );
+    }
+}